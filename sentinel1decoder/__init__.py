@@ -1,6 +1,42 @@
 #
 
-from .l0decoder import Level0Decoder
+from .l0decoder import Level0Decoder, FdbaqPacketIterator, StreamingHeaderParser, decode_measurement, decode_file_to_npy, decode_iw_subswaths, summarize_file
 from .l0file import Level0File
+from .packet_headers import PacketHeaders
+from .product import Product
+from ._user_data_decoder import (
+    BaqMode,
+    decode_single_fdbaq_packet,
+    decode_single_fdbaq_packet_into,
+    decode_single_fdbaq_packet_downsampled,
+    decode_single_fdbaq_packet_with_brc,
+    decode_single_fdbaq_packet_with_thidx,
+    decode_single_fdbaq_packet_brc_hist,
+    decode_single_fdbaq_packet_split,
+    decode_single_fdbaq_packet_channels,
+    decode_single_fdbaq_packet_magnitude,
+    decode_single_fdbaq_packet_phase,
+    decode_single_fdbaq_packet_hash,
+    decode_fdbaq_packets_from_bounds,
+    decode_fdbaq_packets_async,
+    decode_fdbaq_packets_from_ndarray,
+    decode_fdbaq_packets_to_arrow,
+    decode_single_fdbaq_packet_partial,
+    decode_single_fdbaq_packet_window,
+    decode_single_fdbaq_packet_with_spans,
+    decode_single_fdbaq_packet_codes,
+    decode_single_fdbaq_packet_quant,
+    decode_single_fdbaq_packet_int16,
+    decode_single_fdbaq_packet_block_stats,
+    decode_single_bypass_packet,
+    decode_single_bypass_packet_channels,
+    decode_calibration_packet,
+    decode_noise_packets,
+    benchmark_decode,
+    warm_up,
+)
+from ._sample_value_reconstruction import reconstruct_channel
+from ._fdbaq_decoder import huffman_table_fingerprint
+from . import exceptions
 from . import utilities
 from . import constants
\ No newline at end of file
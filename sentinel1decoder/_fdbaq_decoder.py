@@ -4,10 +4,13 @@ Created on Fri Jul  1 10:37:42 2022.
 
 @author: richa
 """
+import hashlib
 import math
 import logging
 
 from ._sample_code import SampleCode
+from . import constants as cnst
+from .exceptions import BrcMismatchError, InvalidBrcError, InvalidHuffmanCodeError, UnexpectedEofError
 
 _TREE_BRC_ZERO = (0, (1, (2, 3)))
 _TREE_BRC_ONE = (0, (1, (2, (3, 4))))
@@ -15,199 +18,357 @@ _TREE_BRC_TWO = (0, (1, (2, (3, (4, (5, 6))))))
 _TREE_BRC_THREE = ((0, 1), (2, (3, (4, (5, (6, (7, (8, 9))))))))
 _TREE_BRC_FOUR = ((0, (1, 2)), ((3, 4), ((5, 6), (7, (8, (9, ((10, 11), ((12, 13), (14, 15)))))))))
 
+# Huffman trees indexed by BRC
+_TREES = (_TREE_BRC_ZERO, _TREE_BRC_ONE, _TREE_BRC_TWO, _TREE_BRC_THREE, _TREE_BRC_FOUR)
+
+
+def huffman_codes(huffman_tree):
+    """
+    Find the Huffman code of each magnitude code in a Huffman tree.
+
+    Args:
+        huffman_tree:   Huffman tree for one BRC.
+
+    Returns:
+        A dict of the code for each magnitude code, as a string of "0"s and "1"s.
+    """
+    codes = {}
+
+    def walk(node, prefix):
+        if isinstance(node, int):
+            if node in codes:
+                raise ValueError(f"Magnitude code {node} appears twice in Huffman tree, with codes {codes[node]} and {prefix}")
+            codes[node] = prefix
+        else:
+            walk(node[0], prefix + "0")
+            walk(node[1], prefix + "1")
+
+    walk(huffman_tree, "")
+    return codes
+
+
+def _build_lookup_table(huffman_tree):
+    """
+    Build a lookup table for decoding a sign bit and Huffman code in one step.
+
+    The table is indexed by the next (1 + longest code length) bits of data,
+    and each entry holds the decoded sample code and the total number of bits
+    the sign and code take up. Sample codes are shared between all entries
+    which decode to them, so decoding doesn't create a new object per sample.
+
+    The tree is checked while building the table, and a ValueError is raised if
+    a magnitude code appears in it more than once or two codes collide.
+
+    Args:
+        huffman_tree:   Huffman tree for one BRC.
+
+    Returns:
+        The number of bits used to index the table, and the table itself.
+    """
+    codes = huffman_codes(huffman_tree)
+
+    num_bits = 1 + max(len(code) for code in codes.values())
+    table = [None] * (1 << num_bits)
+    for sign in (0, 1):
+        for mcode, code in codes.items():
+            length = 1 + len(code)
+            scode = SampleCode(sign, mcode)
+            prefix = (sign << len(code)) | int(code or "0", 2)
+            # Every index beginning with this sign and code decodes to it
+            for suffix in range(1 << (num_bits - length)):
+                index = (prefix << (num_bits - length)) | suffix
+                if table[index] is not None:
+                    raise ValueError(f"Huffman code {code} for magnitude code {mcode} collides with the code for magnitude code {table[index][0].mcode}")
+                table[index] = (scode, length)
+
+    return num_bits, table
+
+
+# Lookup tables indexed by BRC, each built the first time it's needed.
+# Building all five takes about 0.3 ms, and unpickling prebuilt tables about
+# 0.13 ms, so they aren't stored between processes.
+_LOOKUP_TABLES = [None] * len(_TREES)
+
+
+def get_lookup_table(brc):
+    """
+    Get the lookup table for a BRC, building it if this is the first use.
+
+    Args:
+        brc:    Bit Rate Code, 0-4.
+
+    Returns:
+        The number of bits used to index the table, and the table itself.
+    """
+    if _LOOKUP_TABLES[brc] is None:
+        _LOOKUP_TABLES[brc] = _build_lookup_table(_TREES[brc])
+    return _LOOKUP_TABLES[brc]
+
+
+def huffman_table_fingerprint(brc: int) -> str:
+    """
+    Get a fingerprint of the lookup table built for a BRC.
+
+    Two installations which build identical tables give the same fingerprint,
+    so this can be used to check a build or fork decodes identically, or to
+    detect an accidental edit to the Huffman trees.
+
+    Args:
+        brc:    Bit Rate Code, 0-4.
+
+    Returns:
+        A hex string hash of the table entries.
+    """
+    num_bits, table = get_lookup_table(brc)
+    digest = hashlib.blake2b(digest_size=16)
+    digest.update(bytes([num_bits]))
+    for scode, length in table:
+        digest.update(bytes([scode.sign, scode.mcode, length]))
+    return digest.hexdigest()
+
 class FDBAQDecoder:
     """Extracts sample codes from Sentinel-1 packets."""
 
-    def __init__(self, data, num_quads):
+    def __init__(
+        self,
+        data,
+        num_quads,
+        partial=False,
+        block_size=cnst.BAQ_BLOCK_SIZE,
+        channel_order=cnst.NOMINAL_CHANNEL_ORDER,
+        channel_spans=None,
+        strict=False
+    ):
         # TODO: Convert to proper Huffman implementation
         self._bit_counter = 0
         self._byte_counter = 0
         self._data = data
         self._num_quads = num_quads
+        self._block_size = block_size
+        self._strict = strict
 
-        self._num_baq_blocks = math.ceil(num_quads/128)
+        self._num_baq_blocks = math.ceil(num_quads/block_size)
         self._brc = []
         self._thidx = []
+        self._channel_end_bytes = []
+        self._channel_name = None
 
         self._i_evens_scodes = []
         self._i_odds_scodes = []
         self._q_evens_scodes = []
         self._q_odds_scodes = []
         
+        channels = {
+            "IE": self._i_evens_scodes,
+            "IO": self._i_odds_scodes,
+            "QE": self._q_evens_scodes,
+            "QO": self._q_odds_scodes
+        }
+        if not sorted(channel_order) == sorted(channels):
+            raise ValueError(f"Channel order {channel_order} must contain each of {tuple(channels)} once")
+        if channel_spans is not None and not len(channel_spans) == 4:
+            raise ValueError(f"Expected a byte span for each of the 4 channels, received {len(channel_spans)}")
+        self._channel_spans = channel_spans
+
         logging.debug(f"Created FDBAQ decoder. Numquads={num_quads} NumBAQblocks={self._num_baq_blocks}")
 
-        # TODO: Lots of repetition here, break into function(s)
-        # Channel 1 - IE
+        try:
+            # In the nominal IE, IO, QE, QO order, each Bit Rate Code is in the
+            # first three bits of each IE block and each THIDX Code is in the
+            # first eight bits of each QE block. Other orders keep them in the
+            # first and third channels stored.
+            for position, channel_name in enumerate(channel_order):
+                if channel_spans is not None:
+                    # Only let each channel read its own bytes, so a misread
+                    # in one channel can't carry over into the next
+                    channel_start = sum(channel_spans[:position])
+                    self._byte_counter = channel_start
+                    self._bit_counter = 0
+                    self._data = data[:channel_start + channel_spans[position]]
+                self._decode_channel(channel_name, channels[channel_name], read_brc=position == 0, read_thidx=position == 2)
+            self._data = data
+        except IndexError as e:
+            # We've run out of data before decoding all the expected samples.
+            if not partial:
+                if isinstance(e, UnexpectedEofError):
+                    raise
+                raise UnexpectedEofError(self._channel_name, self._byte_counter, len(self._data)) from e
+            self._data = data
+            logging.warning(f"Packet data ended after {self.get_num_quads_decoded} of {num_quads} quads")
+        else:
+            self._check_unused_data()
+
+    def _decode_channel(self, channel_name, scodes, read_brc=False, read_thidx=False):
+        """
+        Extract the sample codes for one channel from the packet data.
+
+        Each channel ends on a 16-bit word boundary, and the read position is
+        left at the start of the next channel.
+
+        Args:
+            channel_name:   Name of the channel, used for logging.
+            scodes:         List to append the extracted sample codes to.
+            read_brc:       Read a Bit Rate Code from the start of each block.
+            read_thidx:     Read a Threshold Index from the start of each block.
+        """
+        self._channel_name = channel_name
         values_processed_count = 0
         for block_index in range(self._num_baq_blocks):
-            logging.debug(f"Starting IE block {block_index+1} of {self._num_baq_blocks}, processing {min(128, self._num_quads-values_processed_count)} vals")
+            logging.debug(f"Starting {channel_name} block {block_index+1} of {self._num_baq_blocks}, processing {min(self._block_size, self._num_quads-values_processed_count)} vals")
 
-            # Each Bit Rate Code is in the first three bits of each IE block
-            brc = self._read_brc()
-            self._brc.append(brc)
+            if read_brc:
+                self._brc.append(self._read_brc())
 
-            # The BRC determines which type of Huffman encoding we're using
-            # Ref. SAR Space Protocol Data Unit p.71
-            if self._brc[block_index] == 0:
-                this_huffman_tree = _TREE_BRC_ZERO
-            elif self._brc[block_index] == 1:
-                this_huffman_tree = _TREE_BRC_ONE
-            elif self._brc[block_index] == 2:
-                this_huffman_tree = _TREE_BRC_TWO
-            elif self._brc[block_index] == 3:
-                this_huffman_tree = _TREE_BRC_THREE
-            elif self._brc[block_index] == 4:
-                this_huffman_tree = _TREE_BRC_FOUR
-            else:
-                logging.error(f"Unrecognized BAQ mode code {self._brc[block_index]}")
-
-            # Each baq block contains 128 hcodes, except the last
-            for i in range(min(128, self._num_quads-values_processed_count)):
-                sign = self._next_bit()
-
-                # Recursively step through our Huffman tree.
-                # We know we've reached the end when our current node is an
-                # integer rather than a tuple.
-                current_node = this_huffman_tree
-                while not isinstance(current_node, int):
-                    current_node = current_node[self._next_bit()]
-                    if current_node is None:
-                        raise ValueError
-                self._i_evens_scodes.append(SampleCode(sign, current_node))
-                values_processed_count = values_processed_count+1
-
-        # Channel 2 - IO
-        # Move counters to next 16-bit word boundary
-        logging.debug(f"Finished block: bit_counter={self._bit_counter} byte_counter={self._byte_counter}")
-        if not self._bit_counter == 0:
-            self._bit_counter = 0
-            self._byte_counter += 1
-        self._byte_counter = math.ceil(self._byte_counter / 2) * 2
-        logging.debug(f"Moved counters: bit_counter={self._bit_counter} byte_counter={self._byte_counter}")
-        
-        values_processed_count = 0
-        for block_index in range(self._num_baq_blocks):
-            logging.debug(f"Starting IO block {block_index+1} of {self._num_baq_blocks}, processing {min(128, self._num_quads-values_processed_count)} vals")
+            if read_thidx:
+                self._thidx.append(self._read_thidx())
 
             # The BRC determines which type of Huffman encoding we're using
             # Ref. SAR Space Protocol Data Unit p.71
-            if self._brc[block_index] == 0:
-                this_huffman_tree = _TREE_BRC_ZERO
-            elif self._brc[block_index] == 1:
-                this_huffman_tree = _TREE_BRC_ONE
-            elif self._brc[block_index] == 2:
-                this_huffman_tree = _TREE_BRC_TWO
-            elif self._brc[block_index] == 3:
-                this_huffman_tree = _TREE_BRC_THREE
-            elif self._brc[block_index] == 4:
-                this_huffman_tree = _TREE_BRC_FOUR
-            else:
-                logging.error(f"Unrecognized BAQ mode code {self._brc[block_index]}")
-
-            # Each baq block contains 128 hcodes, except the last
-            for i in range(min(128, self._num_quads-values_processed_count)):
-                sign = self._next_bit()
-
-                # Recursively step through our Huffman tree.
-                # We know we've reached the end when our current node is an
-                # integer rather than a tuple.
-                current_node = this_huffman_tree
-                while not isinstance(current_node, int):
-                    current_node = current_node[self._next_bit()]
-                    if current_node is None:
-                        raise ValueError
-                self._i_odds_scodes.append(SampleCode(sign, current_node))
+            # Only the first channel's blocks hold a BRC, so there's no copy in
+            # the other channels to check it against. In strict mode each
+            # channel's padding is checked instead.
+            brc = self._brc[block_index]
+            if brc >= len(_TREES):
+                logging.error(f"Invalid Bit Rate Code {brc}")
+                raise InvalidBrcError(brc)
+            this_huffman_tree = _TREES[brc]
+            num_lookup_bits, lookup_table = get_lookup_table(brc)
+
+            # Each baq block contains block_size hcodes, except the last
+            for i in range(min(self._block_size, self._num_quads-values_processed_count)):
+                scodes.append(self._read_scode(this_huffman_tree, num_lookup_bits, lookup_table))
                 values_processed_count = values_processed_count+1
 
-        # Channel 3 -QE
-        # Move counters to next 16-bit word boundary
+        if self._strict:
+            self._check_padding(channel_name)
+        self._move_to_next_word()
+        if self._byte_counter > len(self._data):
+            raise UnexpectedEofError(channel_name, self._byte_counter, len(self._data))
+        self._channel_end_bytes.append(self._byte_counter)
+
+    def _check_unused_data(self):
+        """
+        Warn if the channels end well before the end of the packet data.
+
+        Packets are padded to a multiple of 4 bytes, so after the last channel
+        ends on a 16-bit word boundary there should be at most 2 bytes left.
+        Any more suggests a channel was decoded misaligned.
+        """
+        unused_bytes = len(self._data) - self._byte_counter
+        if unused_bytes > 2:
+            logging.warning(
+                f"FDBAQ channels ended at bytes {self._channel_end_bytes}, leaving {unused_bytes} "
+                f"of {len(self._data)} bytes of user data unused. The channels may be misaligned."
+            )
+
+    def _check_padding(self, channel_name):
+        """
+        Check the bits between the end of a channel and the next 16-bit word are zero.
+
+        Raises:
+            BrcMismatchError: If any padding bit is set.
+        """
+        padding = 0
+        first_byte = self._byte_counter
+        if not self._bit_counter == 0:
+            padding |= self._data[self._byte_counter] & (0xff >> self._bit_counter)
+            first_byte += 1
+        word_end = math.ceil(first_byte / 2) * 2
+        for byte in self._data[first_byte:word_end]:
+            padding |= byte
+
+        if padding:
+            logging.error(f"Non-zero padding at the end of channel {channel_name}")
+            raise BrcMismatchError(channel_name, self._num_baq_blocks - 1, self._byte_counter)
+
+    def _read_scode(self, huffman_tree, num_lookup_bits, lookup_table):
+        """
+        Read a sign bit and Huffman-encoded magnitude code from the data.
+
+        Args:
+            huffman_tree:       Huffman tree for the current BRC.
+            num_lookup_bits:    Number of bits used to index lookup_table.
+            lookup_table:       Lookup table built from huffman_tree.
+
+        Returns:
+            The sample code read. This is shared with the lookup table, so
+            must not be modified.
+        """
+        num_bytes = (self._bit_counter + num_lookup_bits + 7) // 8
+        if self._byte_counter + num_bytes > len(self._data):
+            # Too close to the end of the data to read a full table index
+            return self._read_scode_from_tree(huffman_tree)
+
+        chunk = int.from_bytes(self._data[self._byte_counter:self._byte_counter+num_bytes], 'big')
+        index = (chunk >> (8*num_bytes - self._bit_counter - num_lookup_bits)) & ((1 << num_lookup_bits) - 1)
+        entry = lookup_table[index]
+        if entry is None:
+            raise InvalidHuffmanCodeError(self._channel_name, self._byte_counter)
+        scode, length = entry
+
+        bit_position = self._bit_counter + length
+        self._byte_counter += bit_position // 8
+        self._bit_counter = bit_position % 8
+        return scode
+
+    def _read_scode_from_tree(self, huffman_tree):
+        """
+        Read a sign bit and magnitude code by stepping through the Huffman tree.
+
+        Args:
+            huffman_tree:   Huffman tree for the current BRC.
+
+        Returns:
+            The sample code read.
+        """
+        sign = self._next_bit()
+
+        # Recursively step through our Huffman tree.
+        # We know we've reached the end when our current node is an
+        # integer rather than a tuple.
+        current_node = huffman_tree
+        while not isinstance(current_node, int):
+            current_node = current_node[self._next_bit()]
+            if current_node is None:
+                raise InvalidHuffmanCodeError(self._channel_name, self._byte_counter)
+        return SampleCode(sign, current_node)
+
+    def _move_to_next_word(self):
+        """Move the read position to the next 16-bit word boundary."""
         logging.debug(f"Finished block: bit_counter={self._bit_counter} byte_counter={self._byte_counter}")
         if not self._bit_counter == 0:
             self._bit_counter = 0
             self._byte_counter += 1
         self._byte_counter = math.ceil(self._byte_counter / 2) * 2
         logging.debug(f"Moved counters: bit_counter={self._bit_counter} byte_counter={self._byte_counter}")
-        
-        values_processed_count = 0
-        for block_index in range(self._num_baq_blocks):
-            logging.debug(f"Starting QE block {block_index+1} of {self._num_baq_blocks}, processing {min(128, self._num_quads-values_processed_count)} vals")
 
-            # Each THIDX Code is in the first eight bits of each IE block
-            this_thidx = self._read_thidx()
-            self._thidx.append(this_thidx)
-
-            # The BRC determines which type of Huffman encoding we're using
-            # Ref. SAR Space Protocol Data Unit p.71
-            if self._brc[block_index] == 0:
-                this_huffman_tree = _TREE_BRC_ZERO
-            elif self._brc[block_index] == 1:
-                this_huffman_tree = _TREE_BRC_ONE
-            elif self._brc[block_index] == 2:
-                this_huffman_tree = _TREE_BRC_TWO
-            elif self._brc[block_index] == 3:
-                this_huffman_tree = _TREE_BRC_THREE
-            elif self._brc[block_index] == 4:
-                this_huffman_tree = _TREE_BRC_FOUR
-            else:
-                logging.error(f"Unrecognized BAQ mode code {self._brc[block_index]}")
-
-            # Each baq block contains 128 hcodes, except the last
-            for i in range(min(128, self._num_quads-values_processed_count)):
-                sign = self._next_bit()
-
-                # Recursively step through our Huffman tree.
-                # We know we've reached the end when our current node is an
-                # integer rather than a tuple.
-                current_node = this_huffman_tree
-                while not isinstance(current_node, int):
-                    current_node = current_node[self._next_bit()]
-                    if current_node is None:
-                        raise ValueError
-                self._q_evens_scodes.append(SampleCode(sign, current_node))
-                values_processed_count = values_processed_count+1
+    @property
+    def get_num_quads_decoded(self):
+        """Get the number of quads for which all four channels were decoded."""
+        return min(
+            len(self._i_evens_scodes),
+            len(self._i_odds_scodes),
+            len(self._q_evens_scodes),
+            len(self._q_odds_scodes)
+        )
 
-        # Channel 4 - QO
-        # Move counters to next 16-bit word boundary
-        logging.debug(f"Finished block: bit_counter={self._bit_counter} byte_counter={self._byte_counter}")
-        if not self._bit_counter == 0:
-            self._bit_counter = 0
-            self._byte_counter += 1
-        self._byte_counter = math.ceil(self._byte_counter / 2) * 2
-        logging.debug(f"Moved counters: bit_counter={self._bit_counter} byte_counter={self._byte_counter}")
-        
-        values_processed_count = 0
-        for block_index in range(self._num_baq_blocks):
-            logging.debug(f"Starting QO block {block_index+1} of {self._num_baq_blocks}, processing {min(128, self._num_quads-values_processed_count)} vals")
+    @property
+    def get_channel_end_bytes(self):
+        """Get the byte offset at which each decoded channel ends, after word alignment."""
+        return self._channel_end_bytes
 
-            # The BRC determines which type of Huffman encoding we're using
-            # Ref. SAR Space Protocol Data Unit p.71
-            if self._brc[block_index] == 0:
-                this_huffman_tree = _TREE_BRC_ZERO
-            elif self._brc[block_index] == 1:
-                this_huffman_tree = _TREE_BRC_ONE
-            elif self._brc[block_index] == 2:
-                this_huffman_tree = _TREE_BRC_TWO
-            elif self._brc[block_index] == 3:
-                this_huffman_tree = _TREE_BRC_THREE
-            elif self._brc[block_index] == 4:
-                this_huffman_tree = _TREE_BRC_FOUR
-            else:
-                logging.error(f"Unrecognized BAQ mode code {self._brc[block_index]}")
-
-            # Each baq block contains 128 hcodes, except the last
-            for i in range(min(128, self._num_quads-values_processed_count)):
-                sign = self._next_bit()
-
-                # Recursively step through our Huffman tree.
-                # We know we've reached the end when our current node is an
-                # integer rather than a tuple.
-                current_node = this_huffman_tree
-                while not isinstance(current_node, int):
-                    current_node = current_node[self._next_bit()]
-                    if current_node is None:
-                        raise ValueError
-                self._q_odds_scodes.append(SampleCode(sign, current_node))
-                values_processed_count = values_processed_count+1
+    @property
+    def get_channel_span_matches(self):
+        """
+        Get whether each decoded channel used exactly its expected byte span.
+
+        Only available if channel spans were supplied. A channel which ends
+        early suggests it was misread.
+        """
+        if self._channel_spans is None:
+            raise ValueError("No channel spans were supplied")
+        expected_ends = [sum(self._channel_spans[:i+1]) for i in range(len(self._channel_end_bytes))]
+        return [end == expected for end, expected in zip(self._channel_end_bytes, expected_ends)]
 
     @property
     def get_brcs(self):
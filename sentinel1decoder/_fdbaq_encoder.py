@@ -0,0 +1,52 @@
+import math
+
+from . import constants as cnst
+from ._fdbaq_decoder import huffman_codes, _TREES
+
+
+def encode_fdbaq_packet(mcodes, signs, brcs, thidxs, block_size=cnst.BAQ_BLOCK_SIZE) -> bytes:
+    """Encode sample codes into FDBAQ user data.
+
+    The inverse of decode_single_fdbaq_packet_codes(), intended for generating
+    synthetic packets to test the decoder with. Each BRC is written at the
+    start of its IE block and each THIDX at the start of its QE block, and
+    each channel is padded to a 16-bit word boundary.
+
+    Args:
+        mcodes:     The magnitude codes of the IE, IO, QE and QO channels, as
+                    four equal-length sequences.
+        signs:      The sign bits of each sample, in the same layout as mcodes.
+        brcs:       The BRC of each BAQ block.
+        thidxs:     The THIDX of each BAQ block.
+        block_size: Number of samples in each BAQ block.
+
+    Returns:
+        The encoded user data.
+    """
+    num_quads = len(mcodes[0])
+    num_blocks = math.ceil(num_quads / block_size)
+    if not len(brcs) == len(thidxs) == num_blocks:
+        raise ValueError(f"Expected {num_blocks} BRCs and THIDXs for {num_quads} quads, received {len(brcs)} and {len(thidxs)}")
+
+    codes = [huffman_codes(tree) for tree in _TREES]
+    bits = []
+    for channel_index in range(4):
+        for block_index in range(num_blocks):
+            brc = int(brcs[block_index])
+            if channel_index == 0:
+                bits.append(format(brc, "03b"))
+            elif channel_index == 2:
+                bits.append(format(int(thidxs[block_index]), "08b"))
+
+            for n in range(block_index * block_size, min((block_index + 1) * block_size, num_quads)):
+                mcode = int(mcodes[channel_index][n])
+                if mcode not in codes[brc]:
+                    raise ValueError(f"Magnitude code {mcode} can't be encoded with BRC {brc}")
+                bits.append("1" if signs[channel_index][n] else "0")
+                bits.append(codes[brc][mcode])
+
+        # Pad each channel to the next 16-bit word
+        bits.append("0" * (-sum(len(b) for b in bits) % 16))
+
+    bit_string = "".join(bits)
+    return int(bit_string, 2).to_bytes(len(bit_string) // 8, "big") if bit_string else b""
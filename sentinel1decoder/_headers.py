@@ -7,6 +7,7 @@ Created on Thu Jun 30 18:34:01 2022.
 import logging
 
 from . import constants as cnst
+from .utilities import pri_to_seconds, swst_to_seconds, swl_to_seconds
 
 
 def decode_primary_header(header_bytes: bytes) -> dict:
@@ -27,7 +28,7 @@ def decode_primary_header(header_bytes: bytes) -> dict:
 
     """
     if not len(header_bytes) == 6:
-        logging.ERROR("Primary header must be exactly 6 bytes")
+        logging.error("Primary header must be exactly 6 bytes")
         raise Exception(f"Primary header must be exactly 6 bytes. Received {len(header_bytes)} bytes.")
 
     tmp16 = int.from_bytes(header_bytes[:2], 'big')
@@ -63,7 +64,7 @@ def decode_primary_header(header_bytes: bytes) -> dict:
     return output_dictionary
 
 
-def decode_secondary_header(header_bytes: bytes) -> dict:
+def decode_secondary_header(header_bytes: bytes, reserved_bits: bool = False) -> dict:
     """Decode the Sentinel-1 Space Packet secondary header.
 
     Refer to SAR Space Protocol Data Unit specification document pg.14
@@ -71,12 +72,16 @@ def decode_secondary_header(header_bytes: bytes) -> dict:
 
     Args:
         header_bytes: Set of input bytes. Must contain exactly 62 bytes.
+        reserved_bits: Also record the unused bits of each byte which has any,
+                       in a field per byte named with constants.RESERVED_BITS_FIELD_NAME.
+                       These should be zero, so non-zero values can indicate bit
+                       errors or non-standard data.
 
     Returns:
         A dictionary of secondary header fields.
     """
     if not len(header_bytes) == 62:
-        logging.ERROR("Secondary header must be exactly 62 bytes")
+        logging.error("Secondary header must be exactly 62 bytes")
         raise Exception(f"Secondary header must be exactly 62 bytes. Received {len(header_bytes)} bytes.")
 
     # ---------------------------------------------------------
@@ -106,8 +111,11 @@ def decode_secondary_header(header_bytes: bytes) -> dict:
 
     instrument_config_id = int.from_bytes(header_bytes[16:20], 'big')
 
+    sync_valid = sync == cnst.SYNC_MARKER
+
     output_dictionary.update({
         cnst.SYNC_FIELD_NAME: sync,
+        cnst.SYNC_VALID_FIELD_NAME: sync_valid,
         cnst.DATA_TAKE_ID_FIELD_NAME: data_take_id,
         cnst.ECC_NUM_FIELD_NAME: ecc_number,
         cnst.TEST_MODE_FIELD_NAME: test_mode,
@@ -115,7 +123,7 @@ def decode_secondary_header(header_bytes: bytes) -> dict:
         cnst.INSTRUMENT_CONFIG_ID_FIELD_NAME: instrument_config_id
     })
 
-    if sync != 0x352EF853:
+    if not sync_valid:
         logging.error("Sync marker != 352EF853")
 
     # ---------------------------------------------------------
@@ -177,22 +185,35 @@ def decode_secondary_header(header_bytes: bytes) -> dict:
     rank = header_bytes[43] & 0x1f  # Byte 43 bits 3-7
 
     tmp24 = int.from_bytes(header_bytes[44:47], 'big')
-    pri = tmp24 / cnst.F_REF
+    pri = float(pri_to_seconds(tmp24))
 
     tmp24 = int.from_bytes(header_bytes[47:50], 'big')
-    sampling_window_start_time = tmp24 / cnst.F_REF
+    sampling_window_start_time = float(swst_to_seconds(tmp24))
 
     tmp24 = int.from_bytes(header_bytes[50:53], 'big')
-    sampling_window_length = tmp24/cnst.F_REF
+    sampling_window_length = float(swl_to_seconds(tmp24))
 
     sas_ssbflag = header_bytes[53] >> 7  # Byte 53 Bit 0
     polarisation = (header_bytes[53] >> 4) & 0x07  # Byte 53 Bits 1-3
     temperature_comp = (header_bytes[53] >> 2) & 0x03  # Byte 53 Bits 4-5
     # Byte 53 Bits 6-7 are unused
 
-    # Some extra unimplemented stuff here.
-    # Exact fields used depends on the value of sas_ssbflag
-    # TODO: Implement sas_ssb_message decoding
+    # The remainder of the SAS SSB message depends on the value of sas_ssbflag
+    if sas_ssbflag == 0:
+        # Imaging and noise operation
+        elevation_beam_address = header_bytes[54] >> 4  # Byte 54 Bits 0-3
+        # Byte 54 Bits 4-5 are unused
+        azimuth_beam_address = int.from_bytes(header_bytes[54:56], 'big') & 0x03ff  # Byte 54 Bits 6-7, Byte 55
+        calibration_type = None
+        calibration_beam_address = None
+    else:
+        # Calibration operation
+        elevation_beam_address = None
+        azimuth_beam_address = None
+        # Byte 54 Bit 0 is unused
+        calibration_type = (header_bytes[54] >> 4) & 0x07  # Byte 54 Bits 1-3
+        # Byte 54 Bits 4-5 are unused
+        calibration_beam_address = int.from_bytes(header_bytes[54:56], 'big') & 0x03ff  # Byte 54 Bits 6-7, Byte 55
 
     calibration_mode = header_bytes[56] >> 6  # Byte 56 Bits 0-1
     # Byte 56 Bit 2 is unused
@@ -220,6 +241,10 @@ def decode_secondary_header(header_bytes: bytes) -> dict:
         cnst.SAS_SSB_FLAG_FIELD_NAME: sas_ssbflag,
         cnst.POLARIZATION_FIELD_NAME: polarisation,
         cnst.TEMP_COMP_FIELD_NAME: temperature_comp,
+        cnst.ELEVATION_BEAM_ADDRESS_FIELD_NAME: elevation_beam_address,
+        cnst.AZIMUTH_BEAM_ADDRESS_FIELD_NAME: azimuth_beam_address,
+        cnst.CAL_TYPE_FIELD_NAME: calibration_type,
+        cnst.CAL_BEAM_ADDRESS_FIELD_NAME: calibration_beam_address,
         cnst.CAL_MODE_FIELD_NAME: calibration_mode,
         cnst.TX_PULSE_NUM_FIELD_NAME: tx_pulse_number,
         cnst.SIGNAL_TYPE_FIELD_NAME: signal_type,
@@ -238,8 +263,20 @@ def decode_secondary_header(header_bytes: bytes) -> dict:
         cnst.NUM_QUADS_FIELD_NAME: number_of_quads
     })
 
+    if reserved_bits:
+        for byte_index, mask in _RESERVED_BIT_MASKS[sas_ssbflag].items():
+            output_dictionary[cnst.RESERVED_BITS_FIELD_NAME.format(byte_index)] = header_bytes[byte_index] & mask
+
     # ---------------------------------------------------------
     # End of secondary header information
     # ---------------------------------------------------------
 
     return output_dictionary
+
+
+# Masks of the unused bits in each byte of the secondary header, for each SAS
+# SSB flag value. Only byte 54 differs between imaging and calibration packets.
+_RESERVED_BIT_MASKS = {
+    flag: {15: 0x80, 31: 0x60, 33: 0xff, 43: 0xe0, 53: 0x03, 54: byte_54_mask, 56: 0x20, 57: 0x0e, 61: 0xff}
+    for flag, byte_54_mask in ((0, 0x0c), (1, 0x8c))
+}
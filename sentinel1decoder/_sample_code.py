@@ -9,6 +9,10 @@ Created on Fri Jul  1 23:22:57 2022.
 class SampleCode:
     """Sample code conistsing of sign bit and magnitude code."""
 
+    # A packet can contain tens of thousands of these, so store the fields
+    # inline rather than in a per-instance dict
+    __slots__ = ("sign", "mcode")
+
     def __init__(self, sign, mcode):
         self.sign = sign
         self.mcode = mcode
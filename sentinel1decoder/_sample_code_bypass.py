@@ -50,6 +50,12 @@ def decode_bypass_data(data: bytes, num_quads: int) -> Tuple[float, float, float
     num_words = math.ceil((10/16)*num_quads)  # No. of 16-bit words per channel
     num_bytes = 2*num_words  # No. of 8-bit bytes per channel
 
+    # The last channel only needs to extend as far as its final sample, so
+    # the padding after it may be missing from an exactly-sized buffer
+    required_bytes = 3*num_bytes + math.ceil(10*num_quads/8)
+    if len(data) < required_bytes:
+        raise ValueError(f"Bypass data of {len(data)} bytes is too short for {num_quads} quads, expected at least {required_bytes} bytes")
+
     i_evens = np.zeros(num_quads)
     i_odds = np.zeros(num_quads)
     q_evens = np.zeros(num_quads)
@@ -184,3 +190,37 @@ def decode_bypass_data(data: bytes, num_quads: int) -> Tuple[float, float, float
         index_8bit += 5
 
     return i_evens, i_odds, q_evens, q_odds
+
+
+def bypass_channel_padding(data: bytes, num_quads: int) -> Tuple[np.ndarray, np.ndarray]:
+    """
+    Check the padding after each channel of bypass data.
+
+    Each channel is padded with zero bits to a 16-bit word boundary. The
+    padding after the last channel may be missing from an exactly-sized
+    buffer, in which case only the padding present is counted and checked.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+
+    Returns:
+        The number of padding bits after each of the IE, IO, QE and QO channels.
+        A boolean array which is True for each channel with non-zero padding.
+    """
+    num_bytes = 2*math.ceil((10/16)*num_quads)  # No. of 8-bit bytes per channel
+
+    padding_bits = np.zeros(4, dtype=int)
+    nonzero = np.zeros(4, dtype=bool)
+    for channel in range(4):
+        start_bit = 8*channel*num_bytes + 10*num_quads
+        end_bit = min(8*(channel+1)*num_bytes, 8*len(data))
+        if end_bit <= start_bit:
+            continue
+        first_byte = start_bit // 8
+        last_byte = math.ceil(end_bit / 8)
+        chunk = int.from_bytes(data[first_byte:last_byte], 'big')
+        chunk >>= 8*last_byte - end_bit
+        padding_bits[channel] = end_bit - start_bit
+        nonzero[channel] = chunk & ((1 << padding_bits[channel]) - 1) != 0
+    return padding_bits, nonzero
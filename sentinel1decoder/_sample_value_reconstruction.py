@@ -5,13 +5,45 @@ Created on Fri Jul  1 10:16:29 2022.
 @author: richa
 """
 import logging
+import math
 
 import numpy as np
 
+from typing import Sequence, Tuple, Union
+
 from . import _lookup_tables as lookup
+from . import constants as cnst
+from ._sample_code import SampleCode
+from .exceptions import UnrepresentableSampleError
+
 
+def reconstruct_channel_vals(
+    data,
+    block_brcs,
+    block_thidxs,
+    vals_to_process,
+    block_size=cnst.BAQ_BLOCK_SIZE,
+    lenient=False,
+    vectorized=True
+):
+    """Reconstruct the sample values of one channel from its sample codes.
 
-def reconstruct_channel_vals(data, block_brcs, block_thidxs, vals_to_process):
+    Args:
+        data:           Sample codes for one channel.
+        block_brcs:     BRC of each block.
+        block_thidxs:   THIDX of each block.
+        vals_to_process:    Number of samples in the channel.
+        block_size:     Number of samples in each block.
+        lenient:        Set samples with an unrepresentable combination of
+                        BRC, THIDX and magnitude code to NaN, rather than
+                        raising an error.
+        vectorized:     Reconstruct each block at once using numpy. Blocks
+                        containing unrepresentable samples always fall back
+                        to the per-sample method.
+
+    Returns:
+        An array of the reconstructed sample values.
+    """
     if not len(block_brcs) == len(block_thidxs):
         logging.error("Mismatched lengths of BRC block parameters")
     num_brc_blocks = len(block_brcs)
@@ -23,65 +55,259 @@ def reconstruct_channel_vals(data, block_brcs, block_thidxs, vals_to_process):
 
         brc = int(block_brcs[block_index])
         thidx = int(block_thidxs[block_index])
+        block_len = min(block_size, vals_to_process - n)
 
-        # For each code in the BRC block
-        for i in range(min(128, vals_to_process - n)):
-
-            s_code = data[n]
-
-            if brc == 0:
-                if thidx <= 3:
-                    if s_code.get_mcode < 3:
-                        out_vals[n] = (-1)**s_code.get_sign * s_code.get_mcode
-                    elif s_code.get_mcode == 3:
-                        out_vals[n] = (-1)**s_code.get_sign * lookup.b0[thidx]
-                    else:
-                        logging.error("Unhandled reconstruction case")
-                else:
-                    out_vals[n] = (-1)**s_code.get_sign * lookup.nrl_b0[s_code.get_mcode] * lookup.sf[thidx]
-            elif brc == 1:
-                if thidx <= 3:
-                    if s_code.get_mcode < 4:
-                        out_vals[n] = (-1)**s_code.get_sign * s_code.get_mcode
-                    elif s_code.get_mcode == 4:
-                        out_vals[n] = (-1)**s_code.get_sign * lookup.b1[thidx]
-                    else:
-                        logging.error("Unhandled reconstruction case")
-                else:
-                    out_vals[n] = (-1)**s_code.get_sign * lookup.nrl_b1[s_code.get_mcode] * lookup.sf[thidx]
-            elif brc == 2:
-                if thidx <= 5:
-                    if s_code.get_mcode < 6:
-                        out_vals[n] = (-1)**s_code.get_sign * s_code.get_mcode
-                    elif s_code.get_mcode == 6:
-                        out_vals[n] = (-1)**s_code.get_sign * lookup.b2[thidx]
-                    else:
-                        logging.error("Unhandled reconstruction case")
-                else:
-                    out_vals[n] = (-1)**s_code.get_sign * lookup.nrl_b2[s_code.get_mcode] * lookup.sf[thidx]
-            elif brc == 3:
-                if thidx <= 6:
-                    if s_code.get_mcode < 9:
-                        out_vals[n] = (-1)**s_code.get_sign * s_code.get_mcode
-                    elif s_code.get_mcode == 9:
-                        out_vals[n] = (-1)**s_code.get_sign * lookup.b3[thidx]
-                    else:
-                        logging.error("Unhandled reconstruction case")
-                else:
-                    out_vals[n] = (-1)**s_code.get_sign * lookup.nrl_b3[s_code.get_mcode] * lookup.sf[thidx]
-            elif brc == 4:
-                if thidx <= 8:
-                    if s_code.get_mcode < 15:
-                        out_vals[n] = (-1)**s_code.get_sign * s_code.get_mcode
-                    elif s_code.get_mcode == 15:
-                        out_vals[n] = (-1)**s_code.get_sign * lookup.b4[thidx]
-                    else:
-                        logging.error("Unhandled reconstruction case")
-                else:
-                    out_vals[n] = (-1)**s_code.get_sign * lookup.nrl_b4[s_code.get_mcode] * lookup.sf[thidx]
-            else:
-                logging.error("Unhandled reconstruction case")
+        if vectorized:
+            block = data[n:n+block_len]
+            mcodes = np.fromiter((s.mcode for s in block), dtype=np.intp, count=block_len)
+            signs = np.fromiter((s.sign for s in block), dtype=bool, count=block_len)
+            block_vals = _reconstruct_block(mcodes, signs, brc, thidx)
+            if block_vals is not None:
+                out_vals[n:n+block_len] = block_vals
+                n += block_len
+                continue
 
+        # For each code in the BRC block
+        for i in range(block_len):
+            out_vals[n] = _reconstruct_sample(data[n], brc, thidx, block_index, n, lenient)
             n += 1
 
     return out_vals
+
+
+def reconstruct_channel(
+    signs: Union[Sequence[bool], np.ndarray],
+    mcodes: Union[Sequence[int], np.ndarray],
+    brcs: Union[Sequence[int], np.ndarray],
+    thidxs: Union[Sequence[int], np.ndarray],
+    block_size: int = cnst.BAQ_BLOCK_SIZE,
+    lenient: bool = False,
+    return_saturation: bool = False
+) -> Union[np.ndarray, Tuple[np.ndarray, np.ndarray]]:
+    """Reconstruct the sample values of one channel from arrays of sample codes.
+
+    Allows the reconstruction step to be applied to sample codes decoded by
+    other tools, e.g. the output of decode_single_fdbaq_packet_codes().
+
+    Args:
+        signs:      Sign bit of each sample, True or 1 where negative.
+        mcodes:     Magnitude code of each sample.
+        brcs:       BRC of each block.
+        thidxs:     THIDX of each block.
+        block_size: Number of samples in each block.
+        lenient:    Set samples with an unrepresentable combination of BRC,
+                    THIDX and magnitude code to NaN, rather than raising an error.
+        return_saturation:  Also return a mask of the samples which may be clipped.
+
+    Returns:
+        A float32 array of the reconstructed sample values.
+        If return_saturation is set, a boolean array which is True for each
+        sample whose magnitude code is the largest its block's BRC allows. These
+        are the escape codes of the simple reconstruction method, or the top
+        level of the normal reconstruction method, so the true sample value
+        may have been larger.
+    """
+    signs = np.asarray(signs, dtype=bool)
+    mcodes = np.asarray(mcodes, dtype=np.intp)
+    if not signs.shape == mcodes.shape:
+        raise ValueError(f"Mismatched shapes of signs {signs.shape} and magnitude codes {mcodes.shape}")
+
+    num_vals = len(mcodes)
+    num_blocks = math.ceil(num_vals / block_size)
+    if len(brcs) < num_blocks or len(thidxs) < num_blocks:
+        raise ValueError(f"Expected {num_blocks} BRCs and THIDXs for {num_vals} samples, received {len(brcs)} and {len(thidxs)}")
+
+    out_vals = np.zeros(num_vals, dtype=np.float32)
+    saturated = np.zeros(num_vals, dtype=bool)
+    for block_index in range(num_blocks):
+        start = block_index * block_size
+        stop = min(start + block_size, num_vals)
+        brc = int(brcs[block_index])
+        thidx = int(thidxs[block_index])
+
+        block_vals = _reconstruct_block(mcodes[start:stop], signs[start:stop], brc, thidx)
+        if block_vals is None:
+            block_vals = [
+                _reconstruct_sample(SampleCode(int(signs[n]), int(mcodes[n])), brc, thidx, block_index, n, lenient)
+                for n in range(start, stop)
+            ]
+        out_vals[start:stop] = block_vals
+
+        if brc in _SIMPLE_RECONSTRUCTION_PARAMS:
+            saturated[start:stop] = mcodes[start:stop] == _SIMPLE_RECONSTRUCTION_PARAMS[brc][1]
+
+    if return_saturation:
+        return out_vals, saturated
+    return out_vals
+
+
+def _reconstruct_block(mcodes, signs, brc, thidx):
+    """Reconstruct all the samples of one block at once.
+
+    Args:
+        mcodes: Integer array of the magnitude codes in the block.
+        signs:  Boolean array of the sign bits in the block.
+        brc:    BRC of the block.
+        thidx:  THIDX of the block.
+
+    Returns:
+        An array of the reconstructed sample values, or None if any sample in
+        the block can't be reconstructed.
+    """
+    if brc not in _SIMPLE_RECONSTRUCTION_PARAMS:
+        return None
+    max_thidx, max_mcode, b_table = _SIMPLE_RECONSTRUCTION_PARAMS[brc]
+
+    if thidx <= max_thidx:
+        # Simple reconstruction method
+        if mcodes.size and mcodes.max() > max_mcode:
+            return None
+        vals = mcodes.astype(float)
+        vals[mcodes == max_mcode] = b_table[thidx]
+    else:
+        # Normal reconstruction method
+        nrl_table = get_nrl_table(brc)
+        if thidx >= len(_SF_TABLE) or (mcodes.size and mcodes.max() >= len(nrl_table)):
+            return None
+        vals = nrl_table[mcodes] * _SF_TABLE[thidx]
+
+    # Adding zero turns the -0.0 of negative zero samples into 0.0, matching
+    # the per-sample method
+    return np.where(signs, -vals, vals) + 0.0
+
+
+def _reconstruct_sample(s_code, brc, thidx, block_index, n, lenient):
+    """Reconstruct the value of a single sample.
+
+    Args:
+        s_code:         Sample code to reconstruct.
+        brc:            BRC of the sample's block.
+        thidx:          THIDX of the sample's block.
+        block_index:    Index of the sample's block, for error reporting.
+        n:              Index of the sample in the channel, for error reporting.
+        lenient:        Return NaN rather than raising an error if the sample
+                        can't be reconstructed.
+
+    Returns:
+        The reconstructed sample value.
+    """
+    try:
+        if brc == 0:
+            if thidx <= 3:
+                if s_code.get_mcode < 3:
+                    return (-1)**s_code.get_sign * s_code.get_mcode
+                elif s_code.get_mcode == 3:
+                    return (-1)**s_code.get_sign * lookup.b0[thidx]
+            else:
+                return (-1)**s_code.get_sign * lookup.nrl_b0[s_code.get_mcode] * lookup.sf[thidx]
+        elif brc == 1:
+            if thidx <= 3:
+                if s_code.get_mcode < 4:
+                    return (-1)**s_code.get_sign * s_code.get_mcode
+                elif s_code.get_mcode == 4:
+                    return (-1)**s_code.get_sign * lookup.b1[thidx]
+            else:
+                return (-1)**s_code.get_sign * lookup.nrl_b1[s_code.get_mcode] * lookup.sf[thidx]
+        elif brc == 2:
+            if thidx <= 5:
+                if s_code.get_mcode < 6:
+                    return (-1)**s_code.get_sign * s_code.get_mcode
+                elif s_code.get_mcode == 6:
+                    return (-1)**s_code.get_sign * lookup.b2[thidx]
+            else:
+                return (-1)**s_code.get_sign * lookup.nrl_b2[s_code.get_mcode] * lookup.sf[thidx]
+        elif brc == 3:
+            if thidx <= 6:
+                if s_code.get_mcode < 9:
+                    return (-1)**s_code.get_sign * s_code.get_mcode
+                elif s_code.get_mcode == 9:
+                    return (-1)**s_code.get_sign * lookup.b3[thidx]
+            else:
+                return (-1)**s_code.get_sign * lookup.nrl_b3[s_code.get_mcode] * lookup.sf[thidx]
+        elif brc == 4:
+            if thidx <= 8:
+                if s_code.get_mcode < 15:
+                    return (-1)**s_code.get_sign * s_code.get_mcode
+                elif s_code.get_mcode == 15:
+                    return (-1)**s_code.get_sign * lookup.b4[thidx]
+            else:
+                return (-1)**s_code.get_sign * lookup.nrl_b4[s_code.get_mcode] * lookup.sf[thidx]
+
+    except IndexError:
+        # Magnitude code or THIDX outside the range of the lookup tables
+        pass
+
+    return _unhandled_reconstruction_case(s_code, brc, thidx, block_index, n, lenient)
+
+
+# Per-BRC parameters of the simple reconstruction method: the largest THIDX it
+# applies to, the magnitude code which is looked up rather than used directly,
+# and the lookup table for that code.
+_SIMPLE_RECONSTRUCTION_PARAMS = {
+    0: (3, 3, lookup.b0),
+    1: (3, 4, lookup.b1),
+    2: (5, 6, lookup.b2),
+    3: (6, 9, lookup.b3),
+    4: (8, 15, lookup.b4),
+}
+
+# Normal reconstruction lookup tables indexed by BRC
+_NRL_LISTS = (lookup.nrl_b0, lookup.nrl_b1, lookup.nrl_b2, lookup.nrl_b3, lookup.nrl_b4)
+
+# The above as arrays for indexing a whole block at once, each converted the
+# first time a block with that BRC is reconstructed
+_NRL_TABLES = [None] * len(_NRL_LISTS)
+_SF_TABLE = np.array(lookup.sf)
+
+
+def get_nrl_table(brc):
+    """Get the normal reconstruction lookup table for a BRC as an array.
+
+    Args:
+        brc:    Bit Rate Code, 0-4.
+
+    Returns:
+        The normal reconstruction levels for this BRC, indexed by magnitude code.
+    """
+    if _NRL_TABLES[brc] is None:
+        _NRL_TABLES[brc] = np.array(_NRL_LISTS[brc])
+    return _NRL_TABLES[brc]
+
+
+def _unhandled_reconstruction_case(s_code, brc, thidx, block_index, sample_index, lenient):
+    """Handle a sample code which can't be reconstructed.
+
+    Returns:
+        NaN in lenient mode, otherwise raises an UnrepresentableSampleError.
+    """
+    error = UnrepresentableSampleError(block_index, sample_index, s_code.get_mcode, brc, thidx)
+    if not lenient:
+        logging.error(str(error))
+        raise error
+    logging.warning(str(error))
+    return np.nan
+
+
+def reconstruct_channel_stats(data, block_brcs, block_thidxs, vals_to_process, block_size=cnst.BAQ_BLOCK_SIZE):
+    """Calculate statistics of the reconstructed sample magnitudes in each BAQ block.
+
+    Args:
+        data:           Sample codes for one channel.
+        block_brcs:     BRC of each block.
+        block_thidxs:   THIDX of each block.
+        vals_to_process:    Number of samples in the channel.
+        block_size:     Number of samples in each block.
+
+    Returns:
+        An array of shape (number of blocks, 3) holding the minimum, maximum
+        and mean magnitude of the samples in each block.
+    """
+    magnitudes = np.abs(reconstruct_channel_vals(data, block_brcs, block_thidxs, vals_to_process, block_size))
+
+    num_brc_blocks = len(block_brcs)
+    stats = np.zeros((num_brc_blocks, 3))
+    for block_index in range(num_brc_blocks):
+        block = magnitudes[block_index*block_size:(block_index+1)*block_size]
+        stats[block_index] = block.min(), block.max(), block.mean()
+
+    return stats
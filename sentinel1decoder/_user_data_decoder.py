@@ -1,8 +1,75 @@
+import asyncio
+import enum
+import hashlib
 import logging
+import math
+import mmap
+import statistics
+import time
+import numpy as np
+import pandas as pd
+
+from typing import TYPE_CHECKING, List, Optional, Sequence, Tuple, Union
 
 from . import _sample_value_reconstruction as rec
-from ._fdbaq_decoder import FDBAQDecoder
-from ._sample_code_bypass import decode_bypass_data
+from . import constants as cnst
+from .utilities import calibration_type_str
+from ._fdbaq_decoder import FDBAQDecoder, get_lookup_table, _TREES
+from ._sample_code_bypass import decode_bypass_data, bypass_channel_padding
+from .exceptions import InvalidBaqModeError, InvalidBoundsError
+
+if TYPE_CHECKING:
+    import pyarrow
+
+
+class BaqMode(enum.IntEnum):
+    """BAQ mode codes of the packet secondary header which are in use.
+
+    The field is 5 bits wide, with the remaining codes reserved. Refer to SAR
+    Space Protocol Data Unit specification document pg.56.
+    """
+
+    BYPASS = 0
+    BAQ_3_BIT = 3
+    BAQ_4_BIT = 4
+    BAQ_5_BIT = 5
+    FDBAQ_MODE_0 = 12
+    FDBAQ_MODE_1 = 13
+    FDBAQ_MODE_2 = 14
+
+    @classmethod
+    def from_code(cls, code: int) -> "BaqMode":
+        """
+        Get the BAQ mode for a header code.
+
+        Args:
+            code:   BAQ mode field of the packet secondary header.
+
+        Returns:
+            The BAQ mode.
+
+        Raises:
+            InvalidBaqModeError: If the code is reserved.
+        """
+        try:
+            return cls(int(code))
+        except ValueError:
+            raise InvalidBaqModeError(code) from None
+
+    @property
+    def is_bypass(self) -> bool:
+        """Whether samples are stored as plain 10-bit words (data formats A and B)."""
+        return self is BaqMode.BYPASS
+
+    @property
+    def is_baq(self) -> bool:
+        """Whether samples are block adaptive quantized without Huffman coding (data format C)."""
+        return self in (BaqMode.BAQ_3_BIT, BaqMode.BAQ_4_BIT, BaqMode.BAQ_5_BIT)
+
+    @property
+    def is_fdbaq(self) -> bool:
+        """Whether samples are Huffman coded FDBAQ (data format D)."""
+        return self in (BaqMode.FDBAQ_MODE_0, BaqMode.FDBAQ_MODE_1, BaqMode.FDBAQ_MODE_2)
 
 
 class user_data_decoder:
@@ -12,16 +79,21 @@ class user_data_decoder:
     # SCode extraction and reconstruction classes. It decodes and reconstructs
     # the IE, IO, QE, QO values from a single space packet.
 
-    def __init__(self, data, baq_mode, num_quads):
-        if baq_mode not in (0, 3, 4, 5, 12, 13, 14):
+    def __init__(self, data: bytes, baq_mode: int, num_quads: int, swap: bool = False):
+        try:
+            self.baq_mode = BaqMode.from_code(baq_mode)
+        except InvalidBaqModeError:
             logging.error(f"Unrecognized BAQ mode: {baq_mode}")
-            raise Exception(f"Unrecognized BAQ mode: {baq_mode}")
+            raise
 
-        self.data = data
-        self.baq_mode = baq_mode
+        self.data = as_byte_buffer(data)
         self.num_quads = num_quads
+        self.swap = bool(swap)
+
+        # Number of bytes of user data taken up by the sample channels, once decoded
+        self.consumed_bytes = None
 
-    def decode(self):
+    def decode(self, out: Optional[np.ndarray] = None) -> np.ndarray:
         """Decode the user data according to the specified encoding mode.
 
         Refer to SAR Space Protocol Data Unit specification document pg.56.
@@ -34,68 +106,984 @@ class user_data_decoder:
               to type C, but the samples are also Huffman encoded. This format
               is the one typically used for radar echo data.
 
-        Returns
-        -------
-        IE : TYPE
-            DESCRIPTION.
-        IO : TYPE
-            DESCRIPTION.
-        QE : TYPE
-            DESCRIPTION.
-        QO : TYPE
-            DESCRIPTION.
+        Args:
+            out:    Optional complex array of length num_quads*2 to write the
+                    decoded samples into, instead of allocating a new array.
 
+        Returns:
+            The complex I/Q samples from the packet, alternating between the
+            even- and odd-indexed channels. Packets with no quads give an
+            empty array.
         """
-        # TODO: Finish docstrings
 
         # The decoding method used depends on the BAQ mode used.
         # The BAQ mode used for this packet is specified in the packet header.
-        if self.baq_mode == 0:
+        if self.baq_mode.is_bypass:
             # Bypass data is encoded as a simple list of 10-bit words.
             # No value reconstruction is required in this mode.
 
             IE, IO, QE, QO = decode_bypass_data(self.data, self.num_quads)
+            self.consumed_bytes = 4 * 2 * math.ceil((10/16) * self.num_quads)
 
-        elif self.baq_mode in (3, 4, 5):
+        elif self.baq_mode.is_baq:
             # TODO - Implement Data format type C decoding.
             logging.error("Attempted to decode data format C")
             raise NotImplementedError("Data format C is not implemented yet!")
 
-        elif self.baq_mode in (12, 13, 14):
+        else:
             # FDBAQ data uses various types of Huffman encoding.
+            channel_end_bytes = []
+            IE, IO, QE, QO, _, _ = _decode_fdbaq_channels(self.data, self.num_quads, channel_end_bytes=channel_end_bytes)
+            self.consumed_bytes = channel_end_bytes[-1] if channel_end_bytes else 0
+
+        # If the packet's swap flag is set, the I and Q channels were exchanged
+        if self.swap:
+            IE, IO, QE, QO = QE, QO, IE, IO
+
+        # Re-order the even-indexed and odd-indexed sample channels here.
+        return _interleave_channels(IE, IO, QE, QO, out=out)
+
+
+def decode_single_fdbaq_packet(
+    data: bytes,
+    num_quads: int,
+    dtype: str = "complex128",
+    lenient: bool = False,
+    swap: bool = False,
+    channel_order: Tuple[str, ...] = cnst.NOMINAL_CHANNEL_ORDER,
+    interleave: bool = True,
+    strict: bool = False
+) -> np.ndarray:
+    """Decode the user data of a single FDBAQ-encoded space packet.
+
+    Args:
+        data:       The user data payload of the space packet. May be any object
+                    supporting the buffer protocol, such as bytes, a memoryview
+                    or a contiguous uint8 numpy array.
+        num_quads:  Number of quads in the packet.
+        dtype:      Output data type, either "complex64" or "complex128".
+        lenient:    Set samples which can't be reconstructed to NaN rather than
+                    raising an error.
+        swap:       The packet's swap flag. If set, the I and Q channels are
+                    exchanged before being combined.
+        channel_order:  Order in which the IE, IO, QE and QO channels are stored
+                        in the data, for formats which differ from Sentinel-1.
+                        The BRCs are read from the first channel stored and the
+                        THIDXs from the third.
+        interleave: Alternate between the even- and odd-indexed samples, as in
+                    the original signal. If False, all the even-indexed samples
+                    come first, followed by all the odd-indexed samples, so the
+                    output can be reshaped to (2, num_quads) without copying.
+        strict:     Check that every channel fits the BRCs read from the first
+                    channel, by checking the padding at the end of each channel
+                    is zero. Raises a BrcMismatchError if not. Useful as an
+                    integrity check for suspect data.
+
+    Returns:
+        The complex I/Q samples from the packet.
+    """
+    dtype = check_complex_dtype(dtype)
+    IE, IO, QE, QO, _, _ = _decode_fdbaq_channels(data, num_quads, lenient=lenient, channel_order=channel_order, strict=strict)
+    if swap:
+        IE, IO, QE, QO = QE, QO, IE, IO
+    return _interleave_channels(IE, IO, QE, QO, dtype, interleave=interleave)
+
+
+def decode_single_fdbaq_packet_downsampled(
+    data: bytes,
+    num_quads: int,
+    factor: int,
+    method: str = "decimate"
+) -> np.ndarray:
+    """Decode a single FDBAQ packet at reduced range resolution, e.g. for quick-looks.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+        factor:     Number of samples reduced to each output sample.
+        method:     Either "decimate" to keep every factor-th sample, or "mean"
+                    to average each group of factor samples. A final partial
+                    group is averaged over the samples it has.
+
+    Returns:
+        The complex64 I/Q samples, of length ceil(num_quads * 2 / factor).
+    """
+    if factor < 1:
+        raise ValueError(f"Downsampling factor must be at least 1, received {factor}")
+    samples = decode_single_fdbaq_packet(data, num_quads, dtype="complex64")
+
+    if method == "decimate":
+        return samples[::factor].copy()
+    if method == "mean":
+        if not len(samples):
+            return samples
+        starts = np.arange(0, len(samples), factor)
+        group_sizes = np.diff(np.append(starts, len(samples)))
+        return (np.add.reduceat(samples, starts) / group_sizes).astype(np.complex64)
+    raise ValueError(f"Unrecognized downsampling method {method}, expected 'decimate' or 'mean'")
+
+
+def decode_single_fdbaq_packet_into(data: bytes, num_quads: int, out: np.ndarray) -> np.ndarray:
+    """Decode the user data of a single FDBAQ packet into an existing array.
+
+    Reusing one output array across packets avoids allocating a new array
+    for every packet when streaming through a file.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+        out:        Complex64 or complex128 array of length num_quads*2 to
+                    write the samples into.
+
+    Returns:
+        The out array.
+    """
+    if not out.shape == (2 * num_quads,):
+        raise ValueError(f"Output array has shape {out.shape}, expected {(2 * num_quads,)}")
+    check_complex_dtype(out.dtype)
+    IE, IO, QE, QO, _, _ = _decode_fdbaq_channels(data, num_quads)
+    return _interleave_channels(IE, IO, QE, QO, out=out)
+
+
+def decode_single_bypass_packet(
+    data: bytes,
+    num_quads: int,
+    dtype: str = "complex128",
+    swap: bool = False,
+    return_padding: bool = False
+) -> Union[np.ndarray, Tuple[np.ndarray, np.ndarray, np.ndarray]]:
+    """Decode the user data of a single bypass-encoded space packet.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+        dtype:      Output data type, either "complex64" or "complex128".
+        swap:       The packet's swap flag. If set, the I and Q channels are
+                    exchanged before being combined.
+        return_padding: Also return the padding found after each channel,
+                        for validating synthetic or re-muxed data.
+
+    Returns:
+        The complex I/Q samples from the packet.
+        If return_padding is set, the number of padding bits after each of the
+        IE, IO, QE and QO channels as stored, and a boolean array which is True
+        for each channel whose padding isn't all zeros as the spec requires.
+    """
+    dtype = check_complex_dtype(dtype)
+    data = as_byte_buffer(data)
+    IE, IO, QE, QO = decode_bypass_data(data, num_quads)
+    if swap:
+        IE, IO, QE, QO = QE, QO, IE, IO
+    samples = _interleave_channels(IE, IO, QE, QO, dtype)
+
+    if return_padding:
+        padding_bits, nonzero = bypass_channel_padding(data, num_quads)
+        if nonzero.any():
+            logging.warning(f"Non-zero padding after bypass channels {np.flatnonzero(nonzero).tolist()}")
+        return samples, padding_bits, nonzero
+    return samples
+
+
+def decode_single_bypass_packet_channels(data: bytes, num_quads: int) -> Tuple[np.ndarray, np.ndarray, np.ndarray, np.ndarray]:
+    """Decode a single bypass packet into its four separate sample channels.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+
+    Returns:
+        Float32 arrays of the IE, IO, QE and QO channels, each of length num_quads.
+    """
+    channels = decode_bypass_data(as_byte_buffer(data), num_quads)
+    return tuple(ch.astype(np.float32) for ch in channels)
+
+
+def decode_calibration_packet(
+    data: bytes,
+    num_quads: int,
+    caltyp: int,
+    baq_mode: int = 0,
+    swap: bool = False
+) -> Tuple[np.ndarray, str]:
+    """Decode the user data of a single calibration packet.
+
+    Calibration packets are those with the SAS SSB flag set. Their samples are
+    normally bypass encoded, but the packet's BAQ mode can be supplied if not.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+        caltyp:     Calibration type code from the packet's SAS SSB message.
+        baq_mode:   BAQ mode of the packet.
+        swap:       The packet's swap flag.
+
+    Returns:
+        The complex I/Q samples from the packet.
+        The name of the packet's calibration type, e.g. "Tx Cal".
+    """
+    label = calibration_type_str(caltyp)
+    return user_data_decoder(data, baq_mode, num_quads, swap).decode(), label
+
 
-            # Sample code extraction happens in FDBAQDedcoder __init__ function
-            # The extracted channel SCodes are properties of FDBAQDedcoder
-            scode_extractor = FDBAQDecoder(self.data, self.num_quads)
-            brcs = scode_extractor.get_brcs
-            thidxs = scode_extractor.get_thidxs
-
-            logging.debug(f"Read BRCs: {brcs}")
-            logging.debug(f"Read THIDXs: {thidxs}")
-
-            # Huffman-decoded sample codes are grouped into blocks, and can be
-            # reconstructed using various lookup tables which cross-reference
-            # that Block's Bit-Rate Code (BRC) and Threshold Index (THIDX)
-            IE = rec.reconstruct_channel_vals(
-                scode_extractor.get_s_ie, brcs, thidxs, self.num_quads
-            )
-            IO = rec.reconstruct_channel_vals(
-                scode_extractor.get_s_io, brcs, thidxs, self.num_quads
-            )
-            QE = rec.reconstruct_channel_vals(
-                scode_extractor.get_s_qe, brcs, thidxs, self.num_quads
-            )
-            QO = rec.reconstruct_channel_vals(
-                scode_extractor.get_s_qo, brcs, thidxs, self.num_quads
-            )
+def decode_fdbaq_packets_from_bounds(
+    buffer: Union[bytes, bytearray, memoryview, mmap.mmap, np.ndarray],
+    bounds: Union[Sequence[Tuple[int, int]], np.ndarray],
+    num_quads: int,
+    dtype: str = "complex128",
+    order: str = "C",
+    return_mask: bool = False,
+    interleave: bool = True,
+    profile: bool = False
+) -> Union[np.ndarray, Tuple[np.ndarray, ...]]:
+    """Decode several FDBAQ packets whose user data lies in one shared buffer.
 
+    Each packet's data is read through a view of the buffer rather than being
+    copied out, so this pairs well with a memory-mapped file and the bounds
+    returned by Level0Decoder.user_data_bounds().
+
+    Args:
+        buffer:     Object supporting the buffer protocol holding the data of
+                    all the packets, such as an mmap or bytes.
+        bounds:     Sequence of (offset, length) pairs giving the location of
+                    each packet's user data in the buffer. Bounds which don't
+                    lie within the buffer raise an InvalidBoundsError.
+        num_quads:  Number of quads in each packet.
+        dtype:      Output data type, either "complex64" or "complex128".
+        order:      Memory layout of the output, "C" for row-major or "F" for
+                    column-major.
+        return_mask:    Rather than raising an error on the first packet which
+                        fails to decode, fill that packet's row with NaN and
+                        carry on, so one corrupt packet doesn't lose the rest.
+        interleave: Alternate between the even- and odd-indexed samples in each
+                    row. If False, each row holds all the even-indexed samples
+                    followed by all the odd-indexed samples.
+        profile:    Also time how long each packet takes to decode, to help
+                    find which packets are slow.
+
+    Returns:
+        The complex I/Q samples from the packets, one row per packet.
+        If return_mask is set, a boolean array which is True for each packet
+        decoded successfully.
+        If profile is set, an int64 array of the nanoseconds spent decoding
+        each packet.
+    """
+    view = as_byte_buffer(buffer)
+    output_data = np.zeros([len(bounds), 2 * num_quads], dtype=check_complex_dtype(dtype), order=order)
+    decoded = np.ones(len(bounds), dtype=bool)
+    timings = np.zeros(len(bounds), dtype=np.int64)
+    for packet_index, (start, length) in enumerate(bounds):
+        start_time = time.perf_counter_ns()
+        try:
+            if start < 0 or length < 0 or start + length > len(view):
+                raise InvalidBoundsError(packet_index, start, length, len(view))
+            IE, IO, QE, QO, _, _ = _decode_fdbaq_channels(view[start:start+length], num_quads)
+            _interleave_channels(IE, IO, QE, QO, out=output_data[packet_index], interleave=interleave)
+        except Exception as e:
+            if not return_mask:
+                raise
+            logging.error(f"Failed to decode packet {packet_index} at byte offset {start}\n{e}")
+            output_data[packet_index] = np.nan
+            decoded[packet_index] = False
+        timings[packet_index] = time.perf_counter_ns() - start_time
+
+    outputs = (output_data,)
+    if return_mask:
+        outputs += (decoded,)
+    if profile:
+        outputs += (timings,)
+    return outputs if len(outputs) > 1 else output_data
+
+
+async def decode_fdbaq_packets_async(packets: Sequence[bytes], num_quads: int, dtype: str = "complex128") -> np.ndarray:
+    """Decode a list of FDBAQ packets without blocking the event loop.
+
+    Decoding runs in a worker thread, so other tasks on the event loop keep
+    running while it completes. Decoding is pure Python and holds the GIL, so
+    this doesn't make decoding itself any faster.
+
+    Args:
+        packets:    List of the user data payloads of the packets.
+        num_quads:  Number of quads in each packet.
+        dtype:      Output data type, either "complex64" or "complex128".
+
+    Returns:
+        The complex I/Q samples from the packets, one row per packet.
+    """
+    dtype = check_complex_dtype(dtype)
+    return await asyncio.to_thread(_decode_fdbaq_packet_list, packets, num_quads, dtype)
+
+
+def _decode_fdbaq_packet_list(packets, num_quads: int, dtype: np.dtype) -> np.ndarray:
+    """Decode a list of FDBAQ packets into one array, one row per packet."""
+    output_data = np.zeros([len(packets), 2 * num_quads], dtype=dtype)
+    for packet_index, data in enumerate(packets):
+        IE, IO, QE, QO, _, _ = _decode_fdbaq_channels(data, num_quads)
+        _interleave_channels(IE, IO, QE, QO, out=output_data[packet_index])
+    return output_data
+
+
+def decode_fdbaq_packets_from_ndarray(packets: np.ndarray, num_quads: int, dtype: str = "complex128") -> np.ndarray:
+    """Decode FDBAQ packets held as the rows of a 2D uint8 array.
+
+    Each row is read through a view of the array rather than being copied out.
+
+    Args:
+        packets:    A C-contiguous uint8 array of shape (number of packets,
+                    user data length), with one packet's user data per row.
+        num_quads:  Number of quads in each packet.
+        dtype:      Output data type, either "complex64" or "complex128".
+
+    Returns:
+        The complex I/Q samples from the packets, one row per packet.
+    """
+    packets = np.asarray(packets)
+    if not (packets.ndim == 2 and packets.dtype == np.uint8):
+        raise ValueError(f"Expected a 2D uint8 array of packets, received a {packets.ndim}D {packets.dtype} array")
+    row_length = packets.shape[1]
+    bounds = [(packet_index * row_length, row_length) for packet_index in range(packets.shape[0])]
+    return decode_fdbaq_packets_from_bounds(packets, bounds, num_quads, dtype)
+
+
+def decode_fdbaq_packets_to_arrow(packets: Sequence[bytes], num_quads: Union[int, Sequence[int]]) -> "pyarrow.RecordBatch":
+    """Decode several FDBAQ packets into an Arrow RecordBatch.
+
+    Requires the optional pyarrow dependency. The batch has one row per packet
+    and float32 I and Q columns, each holding that packet's samples as a list.
+    If every packet has the same number of quads the columns are fixed size
+    lists, otherwise they are variable length lists.
+
+    Args:
+        packets:    List of the user data payloads of the packets.
+        num_quads:  Number of quads in each packet, either one value for all
+                    the packets or a sequence with one value per packet.
+
+    Returns:
+        A pyarrow RecordBatch with I and Q columns.
+    """
+    try:
+        import pyarrow as pa
+    except ImportError:
+        raise ImportError("pyarrow is required for Arrow export. Install it with pip install sentinel1decoder[arrow]")
+
+    if np.ndim(num_quads) == 0:
+        num_quads = [num_quads] * len(packets)
+    if not len(num_quads) == len(packets):
+        raise ValueError(f"Received {len(num_quads)} numbers of quads for {len(packets)} packets")
+
+    rows = [decode_single_fdbaq_packet(data, nq, dtype="complex64") for data, nq in zip(packets, num_quads)]
+    samples = np.concatenate(rows) if rows else np.zeros(0, dtype=np.complex64)
+    row_lengths = [len(row) for row in rows]
+
+    columns = []
+    for values in (np.ascontiguousarray(samples.real), np.ascontiguousarray(samples.imag)):
+        values = pa.array(values, type=pa.float32())
+        if len(set(row_lengths)) == 1 and row_lengths[0] > 0:
+            columns.append(pa.FixedSizeListArray.from_arrays(values, row_lengths[0]))
         else:
-            logging.error(f"Attempted to decode using invalid BAQ mode: {self.baq_mode}")
+            offsets = pa.array(np.concatenate([[0], np.cumsum(row_lengths, dtype=np.int32)]), type=pa.int32())
+            columns.append(pa.ListArray.from_arrays(offsets, values))
+    return pa.RecordBatch.from_arrays(columns, names=["I", "Q"])
 
-        # Re-order the even-indexed and odd-indexed sample channels here.
-        decoded_data = []
-        for i in range(len(IE)):
-            decoded_data.append(complex(IE[i], QE[i]))
-            decoded_data.append(complex(IO[i], QO[i]))
 
-        return decoded_data
+def decode_noise_packets(
+    packets: Sequence[bytes],
+    num_quads: Sequence[int],
+    baq_modes: Sequence[int],
+    signal_types: Sequence[int],
+    dtype: str = "complex128"
+) -> Tuple[np.ndarray, np.ndarray]:
+    """Decode the noise packets from a list of packets, separately from the echoes.
+
+    Each packet is decoded using its own BAQ mode, so bursts mixing BAQ modes
+    between echo and noise packets are handled. Noise packets using data
+    format type C (BAQ modes 3-5) are not supported yet, and raise a
+    NotImplementedError.
+
+    Args:
+        packets:        List of the user data payload of each packet.
+        num_quads:      Number of quads in each packet.
+        baq_modes:      BAQ mode of each packet.
+        signal_types:   Signal type of each packet.
+        dtype:          Output data type, either "complex64" or "complex128".
+
+    Returns:
+        The complex I/Q samples from the noise packets, one row per packet and
+        zero-padded to the length of the longest noise packet.
+        The indices in the input list of the noise packets.
+    """
+    noise_indices = np.flatnonzero(np.asarray(signal_types) == cnst.NOISE_SIGNAL_TYPE)
+    num_quads = np.asarray(num_quads)
+    max_quads = num_quads[noise_indices].max() if len(noise_indices) else 0
+
+    output_data = np.zeros([len(noise_indices), 2 * max_quads], dtype=check_complex_dtype(dtype))
+    for row, packet_index in enumerate(noise_indices):
+        nq = num_quads[packet_index]
+        decoder = user_data_decoder(packets[packet_index], baq_modes[packet_index], nq)
+        decoder.decode(out=output_data[row, :2 * nq])
+    return output_data, noise_indices
+
+
+def decode_single_fdbaq_packet_with_brc(data: bytes, num_quads: int) -> Tuple[np.ndarray, np.ndarray]:
+    """Decode a single FDBAQ packet, also returning its Bit Rate Codes.
+
+    The BRC is read from the first three bits of each IE block and applies to
+    the corresponding block of all four channels, so one array is returned.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+
+    Returns:
+        The complex I/Q samples from the packet.
+        A uint8 array of the BRC for each BAQ block, of length ceil(num_quads/128).
+    """
+    IE, IO, QE, QO, brcs, _ = _decode_fdbaq_channels(data, num_quads)
+    return _interleave_channels(IE, IO, QE, QO), np.array(brcs, dtype=np.uint8)
+
+
+def decode_single_fdbaq_packet_with_thidx(data: bytes, num_quads: int) -> Tuple[np.ndarray, np.ndarray]:
+    """Decode a single FDBAQ packet, also returning its Threshold Indices.
+
+    Per the SAR Space Protocol Data Unit specification, the THIDX is read from
+    the first eight bits of each QE block. As with the BRC, it applies to the
+    corresponding block of all four channels.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+
+    Returns:
+        The complex I/Q samples from the packet.
+        A uint8 array of the THIDX for each BAQ block, of length ceil(num_quads/128).
+    """
+    IE, IO, QE, QO, _, thidxs = _decode_fdbaq_channels(data, num_quads)
+    return _interleave_channels(IE, IO, QE, QO), np.array(thidxs, dtype=np.uint8)
+
+
+def decode_single_fdbaq_packet_brc_hist(data: bytes, num_quads: int) -> np.ndarray:
+    """Count the BAQ blocks of a single FDBAQ packet using each Bit Rate Code.
+
+    The sample codes are extracted to find each block's BRC, but aren't
+    reconstructed into sample values.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+
+    Returns:
+        A uint32 array of length 5, holding the number of blocks with each BRC
+        from 0 to 4.
+    """
+    brcs = FDBAQDecoder(as_byte_buffer(data), num_quads).get_brcs
+    return np.bincount(np.array(brcs, dtype=np.int64), minlength=5).astype(np.uint32)
+
+
+def decode_single_fdbaq_packet_split(data: bytes, num_quads: int) -> Tuple[np.ndarray, np.ndarray]:
+    """Decode a single FDBAQ packet into separate I and Q arrays.
+
+    Samples are ordered the same way as the complex output, alternating
+    between the even- and odd-indexed channels.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+
+    Returns:
+        A float32 array of the in-phase samples.
+        A float32 array of the quadrature samples.
+    """
+    IE, IO, QE, QO, _, _ = _decode_fdbaq_channels(data, num_quads)
+
+    i_samples = np.zeros(2 * num_quads, dtype=np.float32)
+    q_samples = np.zeros(2 * num_quads, dtype=np.float32)
+    i_samples[0::2] = IE
+    i_samples[1::2] = IO
+    q_samples[0::2] = QE
+    q_samples[1::2] = QO
+
+    return i_samples, q_samples
+
+
+def decode_single_fdbaq_packet_magnitude(data: bytes, num_quads: int) -> np.ndarray:
+    """Decode a single FDBAQ packet into the magnitude of each sample.
+
+    Equivalent to taking the absolute value of the complex output, without
+    building the complex array first.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+
+    Returns:
+        A float32 array of the sample magnitudes.
+    """
+    i_samples, q_samples = decode_single_fdbaq_packet_split(data, num_quads)
+    return np.hypot(i_samples, q_samples)
+
+
+def decode_single_fdbaq_packet_phase(data: bytes, num_quads: int) -> np.ndarray:
+    """Decode a single FDBAQ packet into the phase of each sample.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+
+    Returns:
+        A float32 array of the sample phases in radians, between -pi and pi.
+    """
+    i_samples, q_samples = decode_single_fdbaq_packet_split(data, num_quads)
+    return np.arctan2(q_samples, i_samples)
+
+
+def decode_single_fdbaq_packet_channels(data: bytes, num_quads: int) -> Tuple[np.ndarray, np.ndarray, np.ndarray, np.ndarray]:
+    """Decode a single FDBAQ packet into its four separate sample channels.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+
+    Returns:
+        Float32 arrays of the IE, IO, QE and QO channels, each of length num_quads.
+    """
+    IE, IO, QE, QO, _, _ = _decode_fdbaq_channels(data, num_quads)
+    return tuple(np.asarray(ch, dtype=np.float32) for ch in (IE, IO, QE, QO))
+
+
+def decode_single_fdbaq_packet_hash(data: bytes, num_quads: int) -> Tuple[np.ndarray, int]:
+    """Decode a single FDBAQ packet, also returning a hash of the decoded samples.
+
+    The hash is a 64-bit BLAKE2b digest of the samples as little-endian
+    complex64 values, so it is stable across platforms and versions. Intended
+    for detecting unintended numerical changes in regression tests.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+
+    Returns:
+        The complex I/Q samples from the packet.
+        The hash of the samples, as an unsigned 64-bit integer.
+    """
+    samples = decode_single_fdbaq_packet(data, num_quads)
+    digest = hashlib.blake2b(samples.astype("<c8").tobytes(), digest_size=8).digest()
+    return samples, int.from_bytes(digest, "little")
+
+
+def decode_single_fdbaq_packet_partial(data: bytes, num_quads: int) -> Tuple[np.ndarray, int]:
+    """Decode as much of a single FDBAQ packet as the data allows.
+
+    Intended for recovering data from truncated packets. Decoding stops at the
+    first point the packet data runs out, and any quads which could not be
+    decoded in all four channels are zero-filled.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+
+    Returns:
+        The complex I/Q samples from the packet, zero-padded to num_quads*2.
+        The number of quads which were successfully decoded.
+    """
+    IE, IO, QE, QO, _, _ = _decode_fdbaq_channels(data, num_quads, partial=True)
+    quads_decoded = len(IE)
+
+    decoded_data = np.zeros(2 * num_quads, dtype=complex)
+    decoded_data[:2 * quads_decoded] = _interleave_channels(IE, IO, QE, QO)
+    return decoded_data, quads_decoded
+
+
+def decode_single_fdbaq_packet_with_spans(
+    data: bytes,
+    num_quads: int,
+    channel_spans: Sequence[int],
+    dtype: str = "complex128"
+) -> Tuple[np.ndarray, np.ndarray]:
+    """Decode a single FDBAQ packet whose channels are known to take up given byte spans.
+
+    Each channel is decoded starting from its expected position, and is
+    stopped from reading past the end of its span, so corruption in one
+    channel can't spread into the channels after it. A channel overrunning
+    its span raises an UnexpectedEofError naming that channel.
+
+    Args:
+        data:           The user data payload of the space packet.
+        num_quads:      Number of quads in the packet.
+        channel_spans:  Number of bytes each of the four channels takes up, in
+                        the order they are stored, including the padding to
+                        the next 16-bit word.
+        dtype:          Output data type, either "complex64" or "complex128".
+
+    Returns:
+        The complex I/Q samples from the packet.
+        A boolean array which is True for each channel which ended exactly at
+        the end of its span.
+    """
+    dtype = check_complex_dtype(dtype)
+    scode_extractor = FDBAQDecoder(as_byte_buffer(data), num_quads, channel_spans=channel_spans)
+    brcs = scode_extractor.get_brcs
+    thidxs = scode_extractor.get_thidxs
+
+    channels = [
+        rec.reconstruct_channel_vals(scodes, brcs, thidxs, num_quads)
+        for scodes in (
+            scode_extractor.get_s_ie,
+            scode_extractor.get_s_io,
+            scode_extractor.get_s_qe,
+            scode_extractor.get_s_qo
+        )
+    ]
+    return _interleave_channels(*channels, dtype=dtype), np.array(scode_extractor.get_channel_span_matches)
+
+
+def decode_single_fdbaq_packet_window(
+    data: bytes,
+    num_quads: int,
+    block_start: int,
+    block_count: int,
+    dtype: str = "complex128"
+) -> np.ndarray:
+    """Decode the samples of a range of BAQ blocks from a single FDBAQ packet.
+
+    FDBAQ data has to be read sequentially, so the whole packet is still
+    Huffman decoded, but only the requested blocks are reconstructed. This is
+    useful for previewing a small range window of a long packet.
+
+    Args:
+        data:           The user data payload of the space packet.
+        num_quads:      Number of quads in the packet.
+        block_start:    Index of the first BAQ block to return.
+        block_count:    Number of BAQ blocks to return. The window is cut short
+                        if it extends past the last block.
+        dtype:          Output data type, either "complex64" or "complex128".
+
+    Returns:
+        The complex I/Q samples from the requested blocks.
+    """
+    dtype = check_complex_dtype(dtype)
+    num_blocks = math.ceil(num_quads / cnst.BAQ_BLOCK_SIZE)
+    if block_start < 0 or block_count < 0 or block_start > num_blocks:
+        raise ValueError(f"Invalid window of {block_count} blocks from block {block_start}, packet has {num_blocks} blocks")
+
+    scode_extractor = FDBAQDecoder(as_byte_buffer(data), num_quads)
+    block_end = min(block_start + block_count, num_blocks)
+    brcs = scode_extractor.get_brcs[block_start:block_end]
+    thidxs = scode_extractor.get_thidxs[block_start:block_end]
+
+    first_quad = block_start * cnst.BAQ_BLOCK_SIZE
+    last_quad = min(block_end * cnst.BAQ_BLOCK_SIZE, num_quads)
+    window_quads = max(last_quad - first_quad, 0)
+
+    channels = [
+        rec.reconstruct_channel_vals(scodes[first_quad:last_quad], brcs, thidxs, window_quads)
+        for scodes in (
+            scode_extractor.get_s_ie,
+            scode_extractor.get_s_io,
+            scode_extractor.get_s_qe,
+            scode_extractor.get_s_qo
+        )
+    ]
+    return _interleave_channels(*channels, dtype=dtype)
+
+
+def decode_single_fdbaq_packet_codes(data: bytes, num_quads: int) -> Tuple[np.ndarray, np.ndarray, np.ndarray, np.ndarray]:
+    """Decode the raw sample codes of a single FDBAQ packet without reconstruction.
+
+    Useful for studying the compressed representation of the data, or trying
+    alternative reconstruction methods.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+
+    Returns:
+        A uint8 array of shape (4, num_quads) of the magnitude codes of the
+        IE, IO, QE and QO channels.
+        A boolean array of the same shape of the sign bits, True where negative.
+        A uint8 array of the BRC for each BAQ block.
+        A uint8 array of the THIDX for each BAQ block.
+    """
+    scode_extractor = FDBAQDecoder(as_byte_buffer(data), num_quads)
+    channels = (
+        scode_extractor.get_s_ie,
+        scode_extractor.get_s_io,
+        scode_extractor.get_s_qe,
+        scode_extractor.get_s_qo
+    )
+
+    mcodes = np.zeros((4, num_quads), dtype=np.uint8)
+    signs = np.zeros((4, num_quads), dtype=bool)
+    for channel_index, scodes in enumerate(channels):
+        mcodes[channel_index] = [s.get_mcode for s in scodes]
+        signs[channel_index] = [s.get_sign for s in scodes]
+
+    brcs = np.array(scode_extractor.get_brcs, dtype=np.uint8)
+    thidxs = np.array(scode_extractor.get_thidxs, dtype=np.uint8)
+    return mcodes, signs, brcs, thidxs
+
+
+def decode_single_fdbaq_packet_quant(data: bytes, num_quads: int) -> Tuple[np.ndarray, np.ndarray, np.ndarray, np.ndarray]:
+    """Decode the quantizer state of every sample of a single FDBAQ packet.
+
+    Like decode_single_fdbaq_packet_codes(), but with the BRC and THIDX of each
+    block repeated for every sample in it, so all four arrays line up sample
+    for sample. Useful as input to machine learning models of the compression.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+
+    Returns:
+        uint8 arrays of shape (4, num_quads) of the BRC, THIDX and magnitude
+        code of each sample of the IE, IO, QE and QO channels, and a boolean
+        array of the same shape of the sign bits, True where negative.
+    """
+    mcodes, signs, brcs, thidxs = decode_single_fdbaq_packet_codes(data, num_quads)
+    sample_brcs = np.repeat(brcs, cnst.BAQ_BLOCK_SIZE)[:num_quads]
+    sample_thidxs = np.repeat(thidxs, cnst.BAQ_BLOCK_SIZE)[:num_quads]
+    return (
+        np.broadcast_to(sample_brcs, mcodes.shape).copy(),
+        np.broadcast_to(sample_thidxs, mcodes.shape).copy(),
+        mcodes,
+        signs
+    )
+
+
+def decode_single_fdbaq_packet_int16(data: bytes, num_quads: int) -> Tuple[np.ndarray, np.ndarray]:
+    """Decode a single FDBAQ packet into compact signed sample codes.
+
+    The samples are not reconstructed. Each value is the sample's raw magnitude
+    code with its sign applied, so is not proportional to the signal and must
+    not be used as a sample value directly. Reconstructing the samples later
+    requires the packet's BRCs and THIDXs, e.g. from
+    decode_single_fdbaq_packet_codes(), which can be passed with the magnitudes
+    and signs of these codes to reconstruct_channel().
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+
+    Returns:
+        An int16 array of the signed codes of the in-phase samples.
+        An int16 array of the signed codes of the quadrature samples.
+        Both alternate between the even- and odd-indexed channels, matching
+        the order of the complex output of decode_single_fdbaq_packet().
+    """
+    mcodes, signs, _, _ = decode_single_fdbaq_packet_codes(data, num_quads)
+    codes = np.where(signs, -mcodes.astype(np.int16), mcodes.astype(np.int16))
+
+    i_codes = np.zeros(2 * num_quads, dtype=np.int16)
+    q_codes = np.zeros(2 * num_quads, dtype=np.int16)
+    i_codes[0::2] = codes[0]
+    i_codes[1::2] = codes[1]
+    q_codes[0::2] = codes[2]
+    q_codes[1::2] = codes[3]
+
+    return i_codes, q_codes
+
+
+def decode_single_fdbaq_packet_block_stats(data: bytes, num_quads: int) -> pd.DataFrame:
+    """Calculate statistics of the reconstructed samples in each BAQ block of a packet.
+
+    Magnitudes are taken over the samples of all four channels in each block,
+    which makes it cheap to build up a map of compression behaviour over a scene.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+
+    Returns:
+        A pandas dataframe with one row per BAQ block, containing the block's
+        BRC and THIDX and the minimum, maximum and mean sample magnitude.
+    """
+    scode_extractor = FDBAQDecoder(as_byte_buffer(data), num_quads)
+    brcs = scode_extractor.get_brcs
+    thidxs = scode_extractor.get_thidxs
+
+    channel_stats = np.stack([
+        rec.reconstruct_channel_stats(scodes, brcs, thidxs, num_quads)
+        for scodes in (
+            scode_extractor.get_s_ie,
+            scode_extractor.get_s_io,
+            scode_extractor.get_s_qe,
+            scode_extractor.get_s_qo
+        )
+    ])
+
+    # Every channel has the same number of samples in a given block, so the
+    # mean of the channel means is the mean over the whole block
+    return pd.DataFrame({
+        cnst.BRC_FIELD_NAME: np.array(brcs, dtype=np.uint8),
+        cnst.THIDX_FIELD_NAME: np.array(thidxs, dtype=np.uint8),
+        cnst.BLOCK_MIN_MAG_FIELD_NAME: channel_stats[:, :, 0].min(axis=0),
+        cnst.BLOCK_MAX_MAG_FIELD_NAME: channel_stats[:, :, 1].max(axis=0),
+        cnst.BLOCK_MEAN_MAG_FIELD_NAME: channel_stats[:, :, 2].mean(axis=0),
+    })
+
+
+def _decode_fdbaq_channels(
+    data: bytes,
+    num_quads: int,
+    partial: bool = False,
+    block_size: int = cnst.BAQ_BLOCK_SIZE,
+    lenient: bool = False,
+    channel_end_bytes: Optional[List[int]] = None,
+    channel_order: Tuple[str, ...] = cnst.NOMINAL_CHANNEL_ORDER,
+    strict: bool = False
+) -> Tuple:
+    """Extract and reconstruct the four sample channels from FDBAQ user data.
+
+    Args:
+        data:       The user data payload of the space packet.
+        num_quads:  Number of quads in the packet.
+        partial:    If the data runs out, return only the quads decoded in all
+                    four channels rather than raising an error.
+        block_size: Number of samples in each BAQ block.
+        lenient:    Set samples which can't be reconstructed to NaN rather than
+                    raising an error.
+        channel_end_bytes:  Optional list to append the byte offset at which each
+                            decoded channel ends to.
+        channel_order:  Order in which the four channels are stored in the data.
+        strict:     Raise a BrcMismatchError if any channel's padding is non-zero.
+
+    Returns:
+        The reconstructed IE, IO, QE, QO channels, followed by the lists of
+        BRCs and THIDXs read from the packet.
+    """
+    # Sample code extraction happens in FDBAQDedcoder __init__ function
+    # The extracted channel SCodes are properties of FDBAQDedcoder
+    scode_extractor = FDBAQDecoder(as_byte_buffer(data), num_quads, partial, block_size, channel_order, strict=strict)
+    brcs = scode_extractor.get_brcs
+    thidxs = scode_extractor.get_thidxs
+    if channel_end_bytes is not None:
+        channel_end_bytes.extend(scode_extractor.get_channel_end_bytes)
+
+    if partial:
+        num_quads = scode_extractor.get_num_quads_decoded
+        num_blocks = math.ceil(num_quads / block_size)
+        brcs = brcs[:num_blocks]
+        thidxs = thidxs[:num_blocks]
+
+    logging.debug(f"Read BRCs: {brcs}")
+    logging.debug(f"Read THIDXs: {thidxs}")
+
+    # Huffman-decoded sample codes are grouped into blocks, and can be
+    # reconstructed using various lookup tables which cross-reference
+    # that Block's Bit-Rate Code (BRC) and Threshold Index (THIDX)
+    IE = rec.reconstruct_channel_vals(scode_extractor.get_s_ie[:num_quads], brcs, thidxs, num_quads, block_size, lenient)
+    IO = rec.reconstruct_channel_vals(scode_extractor.get_s_io[:num_quads], brcs, thidxs, num_quads, block_size, lenient)
+    QE = rec.reconstruct_channel_vals(scode_extractor.get_s_qe[:num_quads], brcs, thidxs, num_quads, block_size, lenient)
+    QO = rec.reconstruct_channel_vals(scode_extractor.get_s_qo[:num_quads], brcs, thidxs, num_quads, block_size, lenient)
+
+    return IE, IO, QE, QO, brcs, thidxs
+
+
+def benchmark_decode(packets: Sequence[bytes], num_quads: int, iterations: int = 5) -> dict:
+    """Measure FDBAQ decoding throughput on the current machine.
+
+    The packets are decoded into one preallocated array once per iteration,
+    after an untimed warm-up pass which builds the lookup tables.
+
+    Args:
+        packets:    List of the user data payloads of FDBAQ packets.
+        num_quads:  Number of quads in each packet.
+        iterations: Number of times to decode the packets.
+
+    Returns:
+        A dict of the mean and median throughput in complex samples decoded
+        per second, and the time taken by each iteration in seconds.
+    """
+    if iterations < 1:
+        raise ValueError(f"At least one iteration is required, received {iterations}")
+
+    output_data = np.zeros([len(packets), 2 * num_quads], dtype=np.complex64)
+
+    def decode_all():
+        for packet_index, data in enumerate(packets):
+            IE, IO, QE, QO, _, _ = _decode_fdbaq_channels(data, num_quads)
+            _interleave_channels(IE, IO, QE, QO, out=output_data[packet_index])
+
+    decode_all()
+    times = []
+    for _ in range(iterations):
+        start = time.perf_counter()
+        decode_all()
+        times.append(time.perf_counter() - start)
+
+    throughputs = [output_data.size / t for t in times]
+    return {
+        "mean_samples_per_second": statistics.mean(throughputs),
+        "median_samples_per_second": statistics.median(throughputs),
+        "iteration_seconds": times,
+    }
+
+
+def warm_up() -> None:
+    """Build all of the FDBAQ decoding and reconstruction lookup tables ahead of time.
+
+    The tables are otherwise built the first time a packet using each BRC is
+    decoded, which makes the first decodes noticeably slower. Calling this at
+    startup moves that cost out of latency-sensitive code.
+    """
+    for brc in range(len(_TREES)):
+        get_lookup_table(brc)
+        rec.get_nrl_table(brc)
+
+
+def as_byte_buffer(data: Union[bytes, bytearray, memoryview, mmap.mmap, np.ndarray]) -> memoryview:
+    """
+    Borrow the bytes of a buffer protocol object without copying them.
+
+    Args:
+        data:   Object supporting the buffer protocol, e.g. bytes, a memoryview
+                or a numpy uint8 array.
+
+    Returns:
+        A memoryview of the data as unsigned bytes.
+    """
+    try:
+        view = memoryview(data)
+    except TypeError:
+        raise TypeError(f"Packet data of type {type(data).__name__} does not support the buffer protocol")
+    if not view.c_contiguous:
+        raise ValueError("Packet data buffer must be contiguous")
+    return view.cast('B')
+
+
+def check_complex_dtype(dtype: Union[str, np.dtype]) -> np.dtype:
+    """
+    Check a requested output data type is a supported complex type.
+
+    Either byte order is accepted, so output can be written directly in a
+    fixed byte order, e.g. ">c8" for big-endian complex64. Samples are
+    converted to the requested byte order as they are written.
+
+    Args:
+        dtype:  Requested data type, e.g. "complex64" or "complex128".
+
+    Returns:
+        The corresponding numpy dtype.
+    """
+    dtype = np.dtype(dtype)
+    if dtype.newbyteorder("=") not in (np.complex64, np.complex128):
+        raise Exception(f"Unsupported output data type {dtype}, expected complex64 or complex128")
+    return dtype
+
+
+def _interleave_channels(IE, IO, QE, QO, dtype=complex, out=None, interleave=True) -> np.ndarray:
+    """Combine the even- and odd-indexed channels into one complex array.
+
+    If out is supplied the channels are written directly into it, otherwise
+    a new array of the given dtype is allocated. If interleave is False, all
+    the even-indexed samples are written before all the odd-indexed samples.
+    """
+    if out is None:
+        out = np.zeros(2 * len(IE), dtype=dtype)
+    if interleave:
+        even, odd = out[0::2], out[1::2]
+    else:
+        even, odd = out[:len(IE)], out[len(IE):]
+    even.real = IE
+    even.imag = QE
+    odd.real = IO
+    odd.imag = QO
+    return out
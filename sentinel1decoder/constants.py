@@ -4,9 +4,24 @@ Created on Thu Jun 30 18:39:18 2022.
 
 @author: richa
 """
-# Constant used to scale several data fields
+import datetime
+
+# Expected value of the secondary header sync marker
+SYNC_MARKER = 0x352EF853
+
+# Number of samples in each BAQ block, except the last block in a packet
+BAQ_BLOCK_SIZE = 128
+
+# Order in which the four sample channels are stored in Sentinel-1 user data
+NOMINAL_CHANNEL_ORDER = ('IE', 'IO', 'QE', 'QO')
+
+# Reference frequency used to scale several data fields, including the
+# PRI, SWST and SWL, which are counts of reference clock periods
 F_REF = 37.53472224 * 1e6
 
+# Fixed delay between the SWST and the first range sample, in seconds
+SWST_DELAY = 320 / (8 * F_REF)
+
 # Useful for processing radar data
 SPEED_OF_LIGHT_MPS = 299792458.0
 TX_FREQ_HZ = 5.405e9
@@ -14,6 +29,31 @@ TX_WAVELENGTH_M = SPEED_OF_LIGHT_MPS / TX_FREQ_HZ
 WGS84_SEMI_MAJOR_AXIS_M = 6378137
 WGS84_SEMI_MINOR_AXIS_M = 6356752.3142
 
+# Datation service reference times
+GPS_EPOCH = datetime.datetime(1980, 1, 6)  # Origin of the packet coarse time
+DATATION_DEFAULT_EPOCH = datetime.datetime(2000, 1, 1)
+
+# Offset of GPS time ahead of UTC, as (UTC date it took effect, seconds) pairs,
+# for converting packet times to UTC. Must be extended for future leap seconds.
+GPS_UTC_LEAP_SECONDS = (
+    (datetime.datetime(2006, 1, 1), 14),
+    (datetime.datetime(2009, 1, 1), 15),
+    (datetime.datetime(2012, 7, 1), 16),
+    (datetime.datetime(2015, 7, 1), 17),
+    (datetime.datetime(2017, 1, 1), 18),
+)
+
+# Signal type codes
+ECHO_SIGNAL_TYPE = 0
+NOISE_SIGNAL_TYPE = 1
+CAL_SIGNAL_TYPES = (8, 9, 10, 11, 12, 15)
+
+# Packet metadata dataframe attrs key for the byte ranges skipped while resyncing
+SKIPPED_REGIONS_ATTR_NAME = 'Skipped Regions'
+
+# Packet metadata dataframe attrs key for the number of truncated packets dropped
+TRUNCATED_PACKETS_ATTR_NAME = 'Truncated Packets'
+
 # Packet metadata dataframe indices
 PACKET_NUM_FIELD_NAME = 'Packet Number'
 BURST_NUM_FIELD_NAME = 'Azimuth Block Number'
@@ -30,6 +70,7 @@ PACKET_DATA_LEN_FIELD_NAME = 'Packet Data Length'
 COARSE_TIME_FIELD_NAME = 'Coarse Time'
 FINE_TIME_FIELD_NAME = 'Fine Time'
 SYNC_FIELD_NAME = 'Sync'
+SYNC_VALID_FIELD_NAME = 'Sync Marker Valid'
 DATA_TAKE_ID_FIELD_NAME = 'Data Take ID'
 ECC_NUM_FIELD_NAME = 'ECC Number'
 TEST_MODE_FIELD_NAME = 'Test Mode'
@@ -54,6 +95,10 @@ SWL_FIELD_NAME = 'SWL'
 SAS_SSB_FLAG_FIELD_NAME = 'SAS SSB Flag'
 POLARIZATION_FIELD_NAME = 'Polarisation' # Can I irritate Americans and Brits AT THE SAME TIME?!
 TEMP_COMP_FIELD_NAME = 'Temperature Compensation'
+ELEVATION_BEAM_ADDRESS_FIELD_NAME = 'Elevation Beam Address'
+AZIMUTH_BEAM_ADDRESS_FIELD_NAME = 'Azimuth Beam Address'
+CAL_TYPE_FIELD_NAME = 'Calibration Type'
+CAL_BEAM_ADDRESS_FIELD_NAME = 'Calibration Beam Address'
 CAL_MODE_FIELD_NAME = 'Calibration Mode'
 TX_PULSE_NUM_FIELD_NAME = 'Tx Pulse Number'
 SIGNAL_TYPE_FIELD_NAME = 'Signal Type'
@@ -61,6 +106,44 @@ SWAP_FLAG_FIELD_NAME = 'Swap Flag'
 SWATH_NUM_FIELD_NAME = 'Swath Number'
 NUM_QUADS_FIELD_NAME = 'Number of Quads'
 
+# Packet metadata dataframe fields holding the unused bits of a secondary header byte
+RESERVED_BITS_FIELD_NAME = 'Reserved Bits (Byte {})'
+
+# Packet metadata dataframe field added by length checks
+USER_DATA_RESIDUAL_FIELD_NAME = 'User Data Residual Bytes'
+
+# Packet metadata dataframe field marking packets with any user data to decode
+HAS_USER_DATA_FIELD_NAME = 'Has User Data'
+
+# Range decimation info field names
+RANGE_DEC_NUMERATOR_FIELD_NAME = "Decimation Ratio Numerator"
+RANGE_DEC_DENOMINATOR_FIELD_NAME = "Decimation Ratio Denominator"
+RANGE_DEC_FILTER_LENGTH_FIELD_NAME = "Filter Length"
+RANGE_DEC_SAMPLE_RATE_FIELD_NAME = "Sample Rate"
+
+# BAQ block statistics field names
+BRC_FIELD_NAME = "BRC"
+THIDX_FIELD_NAME = "THIDX"
+BLOCK_MIN_MAG_FIELD_NAME = "Minimum Magnitude"
+BLOCK_MAX_MAG_FIELD_NAME = "Maximum Magnitude"
+BLOCK_MEAN_MAG_FIELD_NAME = "Mean Magnitude"
+
+# File summary field names
+NUM_PACKETS_FIELD_NAME = "Number of Packets"
+
+# Beam info output dataframe field names
+SUBSWATH_INDEX_FIELD_NAME = "Sub-swath Index"
+
+# Burst grouping output dataframe field names
+BURST_START_FIELD_NAME = "First Packet"
+BURST_STOP_FIELD_NAME = "Last Packet"
+BURST_HAS_ECHO_FIELD_NAME = "Contains Echo"
+BURST_HAS_NOISE_FIELD_NAME = "Contains Noise"
+BURST_HAS_CAL_FIELD_NAME = "Contains Calibration"
+
+# Subcommed data output dataframe index
+SUBCOM_CYCLE_START_FIELD_NAME = "Cycle Start Packet"
+
 # Subcommed data output dataframe field names
 X_POS_FIELD_NAME = "X-axis position ECEF"
 Y_POS_FIELD_NAME = "Y-axis position ECEF"
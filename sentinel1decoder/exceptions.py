@@ -0,0 +1,107 @@
+"""Exceptions raised when packet data can't be decoded."""
+
+
+class DecodeError(ValueError):
+    """Base class for errors decoding the contents of a packet."""
+    pass
+
+
+class InvalidBrcError(DecodeError):
+    """Exception raised when a BAQ block has a Bit Rate Code outside 0-4."""
+
+    def __init__(self, brc: int):
+        self.brc = brc
+        super().__init__(f"Invalid Bit Rate Code {brc}")
+
+
+class InvalidBaqModeError(DecodeError):
+    """Exception raised when a packet has a reserved BAQ mode code."""
+
+    def __init__(self, baq_mode: int):
+        self.baq_mode = baq_mode
+        super().__init__(f"Unrecognized BAQ mode: {baq_mode}")
+
+
+class BrcMismatchError(DecodeError):
+    """Exception raised when a channel doesn't fit the BRCs read from the first channel.
+
+    Only the first channel's blocks hold a BRC, so a mismatch can't be seen
+    block by block. Instead, a channel decoded with the wrong BRCs rarely ends
+    with the zero padding expected before the next 16-bit word, so the block
+    reported is the channel's last.
+    """
+
+    def __init__(self, channel: str, block_index: int, byte: int):
+        self.channel = channel
+        self.block_index = block_index
+        self.byte = byte
+        super().__init__(
+            f"Channel {channel} doesn't match the BRCs read from the first channel: "
+            f"non-zero padding after block {block_index} at byte {byte}"
+        )
+
+
+class InvalidHuffmanCodeError(DecodeError):
+    """Exception raised when packet data doesn't match any Huffman code for its BRC."""
+
+    def __init__(self, channel: str, byte: int):
+        self.channel = channel
+        self.byte = byte
+        super().__init__(f"Invalid Huffman code in channel {channel} at byte {byte}")
+
+
+class UnexpectedEofError(DecodeError, IndexError):
+    """Exception raised when packet data ends before all samples are decoded.
+
+    Also an IndexError, as running off the end of the data was reported as
+    one before this exception was introduced.
+    """
+
+    def __init__(self, channel: str, byte: int, data_len: int):
+        self.channel = channel
+        self.byte = byte
+        self.data_len = data_len
+        super().__init__(f"Packet data ended while decoding channel {channel}, at byte {byte} of {data_len}")
+
+
+class UnrepresentableSampleError(DecodeError):
+    """Exception raised when a sample code can't be reconstructed into a value."""
+
+    def __init__(self, block_index: int, sample_index: int, mcode: int, brc: int, thidx: int):
+        self.block_index = block_index
+        self.sample_index = sample_index
+        self.mcode = mcode
+        self.brc = brc
+        self.thidx = thidx
+        super().__init__(
+            f"Unhandled reconstruction case in block {block_index} at sample {sample_index}: "
+            f"mcode={mcode}, brc={brc}, thidx={thidx}"
+        )
+
+
+class InvalidSyncMarkerError(DecodeError):
+    """Exception raised in strict mode when a packet's sync marker doesn't match."""
+
+    def __init__(self, packet_index: int, offset: int):
+        self.packet_index = packet_index
+        self.offset = offset
+        super().__init__(f"Invalid sync marker in packet {packet_index} at byte offset {offset}")
+
+
+class InvalidBoundsError(DecodeError):
+    """Exception raised when a packet's user data bounds don't lie within the buffer holding it."""
+
+    def __init__(self, packet_index: int, start: int, length: int, buffer_len: int):
+        self.packet_index = packet_index
+        self.start = start
+        self.length = length
+        self.buffer_len = buffer_len
+        super().__init__(
+            f"Bounds of packet {packet_index}, {length} bytes from byte {start}, "
+            f"don't lie within the {buffer_len} byte buffer"
+        )
+
+
+class TruncatedPacketException(DecodeError):
+    """Exception raised when a file ends part-way through a packet"""
+    pass
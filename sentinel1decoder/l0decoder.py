@@ -4,15 +4,31 @@ Created on Wed Jun 29 22:02:54 2022.
 
 @author: richa
 """
+import contextlib
+import functools
+import io
+import itertools
 import logging
+import mmap
+import os
+import threading
 import numpy as np
 import pandas as pd
 
+from concurrent.futures import ProcessPoolExecutor
+
 from . import _headers as hdrs
-from ._user_data_decoder import user_data_decoder
+from ._user_data_decoder import BaqMode, user_data_decoder, check_complex_dtype
 from . import constants as cnst
+from .exceptions import InvalidSyncMarkerError, TruncatedPacketException
+
+from typing import BinaryIO, Callable, Iterator, List, Optional, Tuple, Union
 
-from typing import BinaryIO, Tuple
+# Default minimum total number of quads in a batch for it to be worth decoding
+# with worker processes. Starting the workers takes roughly as long as decoding
+# a few tens of thousands of quads, but this depends on the machine, so it can
+# be overridden with the min_parallel_quads argument of decode_packets.
+_MIN_PARALLEL_QUADS = 50000
 
 class Level0Decoder:
     """Decoder for Sentinel-1 Level 0 files."""
@@ -24,52 +40,268 @@ class Level0Decoder:
 
         self.filename = filename
 
-    def decode_metadata(self) -> pd.DataFrame:
+    def decode_metadata(
+        self,
+        strict: bool = False,
+        partial: bool = False,
+        num_workers: Optional[int] = None,
+        resync: bool = False,
+        reserved_bits: bool = False
+    ) -> pd.DataFrame:
         """Decode the full header of each packet in a Sentinel-1 Level 0 file.
 
         Sentinel-1 Space Packet format consists of a primary header of 6 bytes
         followed by a packet data field. The first 62 bytes of the packet data
         field are taken up by the packet secondary header.
 
+        Packets whose secondary header flag is not set have no secondary header,
+        and their secondary header fields are left empty (NaN) in the output.
+        Packets with no user data after their headers, e.g. header-only
+        housekeeping packets, are marked in the constants.HAS_USER_DATA_FIELD_NAME
+        column.
+
+        Args:
+            strict: Raise an InvalidSyncMarkerError on the first packet whose sync
+                    marker does not match, rather than only flagging it in the output.
+            partial:    If a malformed packet is encountered, return the metadata of
+                        all packets decoded before it rather than raising an error.
+            num_workers:    If supplied, locate all packets first and then decode their
+                            headers in parallel using this many worker processes.
+                            The partial and resync options have no effect in this mode.
+            resync: If a malformed packet or invalid sync marker is encountered, e.g. at
+                    a corrupt boundary between concatenated segments, scan forward to the
+                    next valid sync marker and resume decoding from that packet. The
+                    (start, end) byte ranges skipped are stored in the output's attrs
+                    under constants.SKIPPED_REGIONS_ATTR_NAME.
+            reserved_bits:  Add a column for each secondary header byte with unused bits,
+                            holding those bits. They should be zero, so non-zero values
+                            can point to bit errors or non-standard data.
+
+        If the file ends part way through a packet, that packet is dropped. The
+        number of packets dropped, 0 or 1, is stored in the output's attrs under
+        constants.TRUNCATED_PACKETS_ATTR_NAME.
+
         Returns:
             A Pandas Dataframe containing the decoded metadata.
         """
+        if num_workers is not None:
+            return self._decode_metadata_parallel(strict, num_workers, reserved_bits)
+
         output_row_list = []
+        skipped_regions = []
+        num_truncated = 0
 
         with open(self.filename, 'rb') as f:
             # An input file typically consists of many packets.
             # We don't know how many ahead of time.
             while True:
+                packet_offset = f.tell()
                 try:
-                    output_dictionary_row, _ = self._read_single_packet(f)
+                    output_dictionary_row, packet_data_bytes = self._read_single_packet(f, reserved_bits)
+                    output_dictionary_row[cnst.HAS_USER_DATA_FIELD_NAME] = len(packet_data_bytes) > 0
+                    if resync and not output_dictionary_row.get(cnst.SYNC_VALID_FIELD_NAME, True):
+                        raise ValueError("Invalid sync marker")
                 except NoMorePacketsException as e:
                     break
+                except TruncatedPacketException as e:
+                    logging.warning(f"File ends part-way through packet {len(output_row_list)} at byte offset {packet_offset}, dropping it")
+                    num_truncated = 1
+                    break
+                except Exception as e:
+                    if resync:
+                        next_offset = _find_next_packet(f, packet_offset + 1)
+                        skipped_end = next_offset if next_offset is not None else os.path.getsize(self.filename)
+                        skipped_regions.append((packet_offset, skipped_end))
+                        logging.warning(f"Skipped bytes {packet_offset} to {skipped_end} after failing to decode packet {len(output_row_list)}\n{e}")
+                        if next_offset is None:
+                            break
+                        f.seek(next_offset)
+                        continue
+                    if partial:
+                        logging.error(f"Failed to decode packet {len(output_row_list)} at byte offset {packet_offset}, returning partial metadata\n{e}")
+                        break
+                    raise ValueError(f"Failed to decode packet {len(output_row_list)} at byte offset {packet_offset}: {e}") from e
+                if strict and not output_dictionary_row.get(cnst.SYNC_VALID_FIELD_NAME, True):
+                    raise InvalidSyncMarkerError(len(output_row_list), packet_offset)
                 output_row_list.append(output_dictionary_row)
 
         output_dataframe = pd.DataFrame(output_row_list)
+        output_dataframe.attrs[cnst.TRUNCATED_PACKETS_ATTR_NAME] = num_truncated
+        if resync:
+            output_dataframe.attrs[cnst.SKIPPED_REGIONS_ATTR_NAME] = skipped_regions
+        return output_dataframe
+
+    def packet_offsets(self) -> np.ndarray:
+        """Find the byte offset of the start of each packet in the file.
+
+        Only the 6-byte primary header of each packet is read, so this is much
+        faster than a full metadata decode. The offsets can be used for random
+        access to individual packets. A truncated final packet is excluded.
+
+        Returns:
+            An array of the byte offsets of each packet.
+        """
+        return self._scan_packets()[0]
+
+    def user_data_bounds(self) -> np.ndarray:
+        """Find the location of the user data of each packet in the file.
+
+        Like packet_offsets(), only the primary headers are read. The user data
+        of packets with a secondary header starts after it, and the whole packet
+        data field of packets without one is user data.
+
+        Returns:
+            An array of shape (number of packets, 2) holding the byte offset
+            and length of the user data of each packet.
+        """
+        offsets, data_lengths, secondary_header_flags, _ = self._scan_packets()
+        secondary_header_lengths = np.where(secondary_header_flags, 62, 0)
+        return np.stack([offsets + 6 + secondary_header_lengths, data_lengths - secondary_header_lengths], axis=1)
+
+    def _scan_packets(self) -> Tuple[np.ndarray, np.ndarray, np.ndarray, int]:
+        """
+        Walk the primary headers of the packets in the file.
+
+        Returns:
+            An array of the byte offsets of each complete packet.
+            An array of the packet data length of each complete packet.
+            An array of the secondary header flag of each complete packet.
+            The number of packets cut short by the end of the file, 0 or 1.
+        """
+        offsets = []
+        data_lengths = []
+        secondary_header_flags = []
+        num_truncated = 0
+        file_size = os.path.getsize(self.filename)
+
+        with open(self.filename, 'rb') as f:
+            offset = 0
+            while offset + 6 <= file_size:
+                f.seek(offset)
+                primary_hdr = hdrs.decode_primary_header(f.read(6))
+                packet_length = 6 + primary_hdr[cnst.PACKET_DATA_LEN_FIELD_NAME]
+                if offset + packet_length > file_size:
+                    break
+                offsets.append(offset)
+                data_lengths.append(primary_hdr[cnst.PACKET_DATA_LEN_FIELD_NAME])
+                secondary_header_flags.append(primary_hdr[cnst.SECONDARY_HEADER_FIELD_NAME])
+                offset += packet_length
+
+        if offset < file_size:
+            logging.warning(f"File ends part-way through the packet at byte offset {offset}, dropping it")
+            num_truncated = 1
+
+        return (
+            np.array(offsets, dtype=np.int64),
+            np.array(data_lengths, dtype=np.int64),
+            np.array(secondary_header_flags, dtype=bool),
+            num_truncated
+        )
+
+    def _decode_metadata_parallel(self, strict: bool, num_workers: int, reserved_bits: bool = False) -> pd.DataFrame:
+        """
+        Decode the packet headers in the file in parallel.
+
+        Args:
+            strict:         Raise an InvalidSyncMarkerError on the first packet whose sync
+                            marker does not match.
+            num_workers:    Number of worker processes to use.
+            reserved_bits:  Also record the unused bits of the secondary headers.
+
+        Returns:
+            A Pandas Dataframe containing the decoded metadata.
+        """
+        offsets, data_lengths, _, num_truncated = self._scan_packets()
+
+        # Primary and secondary headers make up the first 68 bytes of a packet,
+        # but packets without a secondary header can be shorter than that
+        header_bytes_list = []
+        with open(self.filename, 'rb') as f:
+            for offset, data_length in zip(offsets, data_lengths):
+                f.seek(offset)
+                header_bytes_list.append(f.read(min(68, 6 + data_length)))
+
+        # As in the sequential decode, a final packet too short to hold the
+        # secondary header it claims to have is treated as truncated
+        if header_bytes_list and len(header_bytes_list[-1]) < 68 and hdrs.decode_primary_header(header_bytes_list[-1][:6])[cnst.SECONDARY_HEADER_FIELD_NAME]:
+            logging.warning(f"File ends part-way through the packet at byte offset {offsets[-1]}, dropping it")
+            header_bytes_list.pop()
+            num_truncated = 1
+
+        chunk_size = max(1, -(-len(header_bytes_list) // num_workers))
+        chunks = [header_bytes_list[i:i+chunk_size] for i in range(0, len(header_bytes_list), chunk_size)]
+        with ProcessPoolExecutor(max_workers=num_workers) as executor:
+            output_row_list = list(itertools.chain.from_iterable(executor.map(functools.partial(_decode_headers, reserved_bits=reserved_bits), chunks)))
+
+        output_dataframe = pd.DataFrame(output_row_list)
+        output_dataframe.attrs[cnst.TRUNCATED_PACKETS_ATTR_NAME] = num_truncated
+        if strict and cnst.SYNC_VALID_FIELD_NAME in output_dataframe:
+            sync_valid = output_dataframe[cnst.SYNC_VALID_FIELD_NAME].fillna(True).astype(bool)
+            invalid_packets = np.flatnonzero(~sync_valid.to_numpy())
+            if len(invalid_packets):
+                packet_index = invalid_packets[0]
+                raise InvalidSyncMarkerError(int(packet_index), int(offsets[packet_index]))
+
         return output_dataframe
 
-    def decode_packets(self, input_header: pd.DataFrame) -> np.array:
+    def decode_packets(
+        self,
+        input_header: pd.DataFrame,
+        dtype: str = "complex128",
+        progress_callback: Optional[Callable[[int], None]] = None,
+        progress_interval: int = 256,
+        cancel_event: Optional[threading.Event] = None,
+        out: Optional[np.ndarray] = None,
+        num_workers: Optional[int] = None,
+        order: str = "C",
+        respect_swap: bool = False,
+        min_parallel_quads: int = _MIN_PARALLEL_QUADS
+    ) -> np.ndarray:
         """Decode the user data payload from the specified space packets.
 
         Packet data typically consists of a single radar echo. SAR images are
-        built from multiple radar echoes.
+        built from multiple radar echoes. Packets which decode_metadata marked
+        as having no user data are skipped, so there is one row of output per
+        remaining packet.
 
         Args:
             input_header:   A DataFrame containing the packets to be processed. Expected usage
                             is to call decode_metadata to return the full set of packets in the
                             file, select the desired packets from these, and supply the result
                             as the input to this function.
+            dtype:          Output data type, either "complex64" or "complex128".
+            progress_callback:  Optional function called with the number of packets decoded
+                                so far, every progress_interval packets and once decoding is
+                                complete. Any exception it raises aborts decoding.
+            progress_interval:  Number of packets decoded between calls to progress_callback.
+            cancel_event:   Optional event which can be set from another thread to stop
+                            decoding early. The rows decoded before cancellation are returned.
+            out:            Optional pre-allocated array to write the output into, of shape
+                            (number of packets, number of quads * 2) and a complex dtype.
+                            If supplied, dtype is ignored.
+            num_workers:    If supplied, read the selected packets first and then decode their
+                            user data in parallel, using a dedicated pool of this many worker
+                            processes which is shut down once decoding is complete.
+            order:          Memory layout of the output, "C" for row-major or "F" for
+                            column-major, e.g. for FFT libraries which prefer each range
+                            sample to be contiguous across packets. Ignored if out is supplied.
+            respect_swap:   Exchange the I and Q channels of packets with the swap flag
+                            set in their secondary header. Off by default, as the flag is
+                            not set in nominal Sentinel-1 products.
+            min_parallel_quads: Minimum total number of quads for num_workers to start
+                                worker processes, as starting them takes longer than
+                                decoding a small batch.
 
         Returns:
             The complex I/Q values outputted by the Sentinel-1 SAR instrument
             and downlinked in the specified space packets.
 
         """
+        input_header = _with_user_data(input_header)
+
         # Check we can output this data as a single block.
         # TODO: More rigorous checks here
         # TODO: Fix checks when only one packet supplied as input_header
-        # TODO: Report progress since this takes a long time
         swath_numbers = input_header[cnst.SWATH_NUM_FIELD_NAME].unique()
         num_quads = input_header[cnst.NUM_QUADS_FIELD_NAME].unique()
         if not len(swath_numbers) == 1:
@@ -83,26 +315,54 @@ class Level0Decoder:
         packets_to_process = len(input_header)
         nq = input_header[cnst.NUM_QUADS_FIELD_NAME].unique()[0]
 
-        output_data = np.zeros([packets_to_process, nq * 2], dtype=(complex))
+        if out is None:
+            output_data = np.zeros([packets_to_process, nq * 2], dtype=check_complex_dtype(dtype), order=order)
+        else:
+            if not out.shape == (packets_to_process, nq * 2):
+                raise Exception(f"Output array has shape {out.shape}, expected {(packets_to_process, nq * 2)}")
+            check_complex_dtype(out.dtype)
+            output_data = out
+
+        # Packets read but not yet decoded, when decoding in parallel
+        pending = []
 
         with open(self.filename, 'rb') as f:
             # Each iteration of the below loop will process one space packet.
             # An input file typically consists of many packets.
             while packet_counter < packets_to_process:
+                if cancel_event is not None and cancel_event.is_set():
+                    logging.warning(f"Decoding cancelled after {packet_counter} of {packets_to_process} packets")
+                    output_data = output_data[:packet_counter]
+                    break
+
                 try:
                     this_header, packet_data_bytes = self._read_single_packet(f)
                 except NoMorePacketsException as e:
                     break
 
+                # Packets without a secondary header can't be matched to the input
+                if not this_header[cnst.SECONDARY_HEADER_FIELD_NAME]:
+                    continue
+
                 # Comparing space packet count is faster than comparing entire row
                 if this_header[cnst.SPACE_PACKET_COUNT_FIELD_NAME] in input_header[cnst.SPACE_PACKET_COUNT_FIELD_NAME].values:
+                    if num_workers is not None:
+                        pending.append((
+                            packet_data_bytes,
+                            this_header[cnst.BAQ_MODE_FIELD_NAME],
+                            this_header[cnst.NUM_QUADS_FIELD_NAME],
+                            respect_swap and this_header[cnst.SWAP_FLAG_FIELD_NAME]
+                        ))
+                        packet_counter += 1
+                        continue
+
                     logging.debug(f"Decoding data from packet: {this_header}")
                     try:
                         baqmod = this_header[cnst.BAQ_MODE_FIELD_NAME]
                         nq = this_header[cnst.NUM_QUADS_FIELD_NAME]
-                        data_decoder = user_data_decoder(packet_data_bytes, baqmod, nq)
-                        this_data_packet = data_decoder.decode()
-                        output_data[packet_counter, :] = this_data_packet
+                        swap = respect_swap and this_header[cnst.SWAP_FLAG_FIELD_NAME]
+                        data_decoder = user_data_decoder(packet_data_bytes, baqmod, nq, swap)
+                        data_decoder.decode(out=output_data[packet_counter, :])
                     except Exception as e:
                         logging.error(f"Failed to process packet {packet_counter} with Space Packet Count {this_header[cnst.SPACE_PACKET_COUNT_FIELD_NAME]}\n{e}")
                         output_data[packet_counter, :] = 0
@@ -112,45 +372,802 @@ class Level0Decoder:
 
                     packet_counter += 1
 
+                    if progress_callback is not None and packet_counter % progress_interval == 0:
+                        progress_callback(packet_counter)
+
+        if num_workers is not None:
+            output_data, packet_counter = self._decode_user_data_parallel(
+                pending, output_data, num_workers, progress_callback, progress_interval, cancel_event, min_parallel_quads
+            )
+
+        if progress_callback is not None and packet_counter % progress_interval != 0:
+            progress_callback(packet_counter)
+
         return output_data
 
-    def _read_single_packet(self, opened_file: BinaryIO) -> Tuple[dict, bytes]:
+    def decode_packets_by_signal_type(
+        self,
+        input_header: pd.DataFrame,
+        signal_types: List[int],
+        **kwargs
+    ) -> Tuple[np.ndarray, pd.Index]:
+        """Decode the user data of the packets with one of the given signal types.
+
+        Args:
+            input_header:   A DataFrame containing the packets to select from, as for
+                            decode_packets.
+            signal_types:   List of signal types to decode. Only packets in input_header
+                            with one of these signal types are decoded.
+            **kwargs:       Passed on to decode_packets.
+
+        Returns:
+            The complex I/Q values of the selected packets, one row per packet.
+            The index of the rows of input_header which were decoded, in the same
+            order as the output data.
+        """
+        input_header = _with_user_data(input_header)
+        input_header = input_header[input_header[cnst.SIGNAL_TYPE_FIELD_NAME].isin(signal_types)]
+        output_data = self.decode_packets(input_header, **kwargs)
+        return output_data, input_header.index[:len(output_data)]
+
+    def _decode_user_data_parallel(
+        self,
+        packets: List[tuple],
+        output_data: np.ndarray,
+        num_workers: int,
+        progress_callback: Optional[Callable[[int], None]],
+        progress_interval: int,
+        cancel_event: Optional[threading.Event],
+        min_parallel_quads: int = _MIN_PARALLEL_QUADS
+    ) -> Tuple[np.ndarray, int]:
+        """
+        Decode the user data of a list of packets in parallel.
+
+        Packets are decoded in chunks of progress_interval packets, and the
+        progress callback and cancel event are checked as each chunk completes.
+        Batches of fewer than min_parallel_quads quads in total are decoded in
+        this process instead, as starting the workers would take longer.
+
+        Args:
+            packets:        List of the user data, BAQ mode, number of quads and
+                            swap flag of each packet.
+            output_data:    Array to write the output into, one row per packet.
+            num_workers:    Number of worker processes to use.
+            progress_callback:  Optional function called with the number of packets decoded.
+            progress_interval:  Number of packets in each chunk.
+            cancel_event:   Optional event which can be set to stop decoding early.
+            min_parallel_quads: Minimum total number of quads to use the workers for.
+
+        Returns:
+            The output data, truncated if decoding was cancelled.
+            The number of packets decoded.
+        """
+        chunks = [packets[i:i+progress_interval] for i in range(0, len(packets), progress_interval)]
+        packet_counter = 0
+
+        executor = None
+        if sum(nq for _, _, nq, _ in packets) >= min_parallel_quads:
+            executor = ProcessPoolExecutor(max_workers=num_workers)
+            decoded_chunks = executor.map(_decode_user_data, chunks)
+        else:
+            decoded_chunks = map(_decode_user_data, chunks)
+
+        try:
+            for rows in decoded_chunks:
+                if cancel_event is not None and cancel_event.is_set():
+                    logging.warning(f"Decoding cancelled after {packet_counter} of {len(packets)} packets")
+                    return output_data[:packet_counter], packet_counter
+
+                for row in rows:
+                    output_data[packet_counter, :] = 0 if row is None else row
+                    packet_counter += 1
+
+                if progress_callback is not None and packet_counter % progress_interval == 0:
+                    progress_callback(packet_counter)
+        finally:
+            if executor is not None:
+                # cancel_futures needs Python 3.9, the minimum in setup.py
+                executor.shutdown(cancel_futures=True)
+
+        return output_data, packet_counter
+
+    def decode_file(
+        self,
+        signal_type: Optional[int] = cnst.ECHO_SIGNAL_TYPE,
+        pad: bool = False,
+        dtype: str = "complex128",
+        check_lengths: bool = False,
+        order: str = "C",
+        respect_swap: bool = False
+    ) -> Tuple[np.ndarray, pd.DataFrame]:
+        """Decode the headers and user data of all packets of one signal type.
+
+        The file is memory-mapped rather than read into memory, so the OS can
+        page it in as required, and each packet's user data is decoded straight
+        from the mapping into its row of the output. If the file ends part-way
+        through a packet, decoding stops cleanly at the last complete packet.
+
+        Args:
+            signal_type:    Signal type of the packets to decode. Defaults to echo
+                            packets. If None, all packets are decoded.
+            pad:    Allow the selected packets to have different numbers of quads. The
+                    output has one column per sample of the longest packet, and shorter
+                    packets are zero-padded. Otherwise mismatched packets raise an error.
+            dtype:  Output data type, either "complex64" or "complex128".
+            check_lengths:  Compare the number of bytes each packet's sample channels
+                            take up to the length of its user data, and add the difference
+                            to the output headers. Packets are padded to a multiple of 4
+                            bytes, so residuals other than 0 or 2 suggest the packet was
+                            decoded misaligned.
+            order:  Memory layout of the output, "C" for row-major or "F" for column-major.
+            respect_swap:   Exchange the I and Q channels of packets with the swap flag set.
+
+        Returns:
+            The complex I/Q values from the selected packets, one row per packet.
+            A DataFrame containing the headers of the selected packets.
+        """
+        with self._mapped_packets(signal_type) as (header_rows, packet_data):
+            return _decode_packet_rows(header_rows, packet_data, pad, dtype, check_lengths, order, respect_swap)
+
+    def decode_subswaths(self, dtype: str = "complex128") -> List[Tuple[np.ndarray, pd.DataFrame]]:
+        """Decode the echoes of each sub-swath in the file into separate arrays.
+
+        Echo packets are grouped by swath number, e.g. into IW1, IW2 and IW3 for
+        IW products, and each group is decoded using each packet's own number
+        of quads. Packets with fewer quads than the longest in their sub-swath
+        are zero-padded.
+
+        Args:
+            dtype:  Output data type, either "complex64" or "complex128".
+
+        Returns:
+            A list with one entry per sub-swath in order of increasing swath
+            number, holding the complex I/Q values of its echoes, one row per
+            packet, and a DataFrame of the headers of those packets.
+        """
+        with self._mapped_packets(cnst.ECHO_SIGNAL_TYPE) as (header_rows, packet_data):
+            swath_numbers = sorted({this_header[cnst.SWATH_NUM_FIELD_NAME] for this_header in header_rows})
+
+            subswaths = []
+            for swath_number in swath_numbers:
+                rows = [i for i, this_header in enumerate(header_rows) if this_header[cnst.SWATH_NUM_FIELD_NAME] == swath_number]
+                subswaths.append(_decode_packet_rows(
+                    [header_rows[i] for i in rows],
+                    [packet_data[i] for i in rows],
+                    True,
+                    dtype
+                ))
+            return subswaths
+
+    @contextlib.contextmanager
+    def _mapped_packets(self, signal_type: Optional[int]) -> Iterator[Tuple[List[dict], List[memoryview]]]:
+        """
+        Memory-map the file and locate the user data of all packets of one signal type.
+
+        Only the headers are read while scanning the file, and the user data of
+        each packet is returned as a view of the mapping rather than a copy.
+        Scanning stops cleanly at a truncated final packet. Packets without a
+        secondary header or without any user data, e.g. header-only
+        housekeeping packets, are skipped.
+
+        Args:
+            signal_type:    Signal type of the packets to read. If None, all
+                            packets are read.
+
+        Yields:
+            A list of the header fields of each packet.
+            A list of views of the user data of each packet, which are only
+            valid until the context exits.
+        """
+        if not os.path.getsize(self.filename):
+            # Empty files can't be memory-mapped
+            yield [], []
+            return
+
+        with open(self.filename, 'rb') as f, mmap.mmap(f.fileno(), 0, access=mmap.ACCESS_READ) as mm:
+            header_rows = []
+            bounds = []
+            offset = 0
+            while True:
+                try:
+                    this_header, user_data_start, user_data_end = _read_packet_header(mm, offset)
+                except NoMorePacketsException:
+                    break
+                except TruncatedPacketException:
+                    logging.warning(f"File ends part-way through packet {len(header_rows)}, stopping")
+                    break
+                offset = user_data_end
+
+                if not this_header[cnst.SECONDARY_HEADER_FIELD_NAME]:
+                    logging.warning(f"Skipping packet {len(header_rows)} with no secondary header")
+                    continue
+
+                if user_data_start == user_data_end:
+                    logging.info(f"Skipping packet with Space Packet Count {this_header[cnst.SPACE_PACKET_COUNT_FIELD_NAME]} with no user data")
+                    continue
+
+                if signal_type is None or this_header[cnst.SIGNAL_TYPE_FIELD_NAME] == signal_type:
+                    header_rows.append(this_header)
+                    bounds.append((user_data_start, user_data_end))
+
+            view = memoryview(mm)
+            packet_data = [view[start:end] for start, end in bounds]
+            try:
+                yield header_rows, packet_data
+            finally:
+                # The mapping can't be closed while any view of it is alive
+                for packet_view in packet_data:
+                    packet_view.release()
+                view.release()
+
+    def decode_packet_at(
+        self,
+        offset: int,
+        num_quads: Optional[int] = None,
+        baq_mode: Optional[int] = None,
+        swap: bool = False,
+        respect_swap: bool = False
+    ) -> np.ndarray:
+        """Decode the user data of the single packet starting at a byte offset.
+
+        The number of quads and BAQ mode are normally read from the packet's
+        secondary header. Some non-standard and test mode packets have no
+        secondary header, in which case the whole packet data field is treated
+        as user data, and the number of quads and BAQ mode must be supplied.
+
+        Args:
+            offset:     Byte offset of the start of the packet, e.g. one of the
+                        values returned by packet_offsets().
+            num_quads:  Number of quads in the packet. Overrides the header value.
+            baq_mode:   BAQ mode of the packet. Overrides the header value.
+            swap:       Whether to swap the I and Q channels.
+            respect_swap:   Use the swap flag from the packet's secondary header
+                            instead, for packets which have one.
+
+        Returns:
+            The complex I/Q samples from the packet.
+        """
+        with open(self.filename, 'rb') as f:
+            f.seek(offset)
+            this_header, packet_data_bytes = self._read_single_packet(f)
+
+        if this_header[cnst.SECONDARY_HEADER_FIELD_NAME]:
+            num_quads = this_header[cnst.NUM_QUADS_FIELD_NAME] if num_quads is None else num_quads
+            baq_mode = this_header[cnst.BAQ_MODE_FIELD_NAME] if baq_mode is None else baq_mode
+            if respect_swap:
+                swap = this_header[cnst.SWAP_FLAG_FIELD_NAME]
+        elif num_quads is None or baq_mode is None:
+            raise ValueError(f"Packet at byte offset {offset} has no secondary header, num_quads and baq_mode must be supplied")
+
+        return user_data_decoder(packet_data_bytes, baq_mode, num_quads, swap).decode()
+
+    def _read_single_packet(self, opened_file: BinaryIO, reserved_bits: bool = False) -> Tuple[dict, bytes]:
         """
         Read a single packet of data from the file.
 
         Args:
             opened_file:    Sentinel-1 RAW file opened in 'rb' mode with read
                             position at the start of a packet
+            reserved_bits:  Also record the unused bits of the secondary header.
 
         Returns:
             A dict of the header data fields for this packet
             The raw bytes of the user data payload for this packet
         """
-        # PACKET PRIMARY HEADER (6 bytes)
-        # First check if we have reached the end of the file
-        data_buffer = opened_file.read(6)
-        if not data_buffer:
-            raise NoMorePacketsException()
+        return _read_single_packet(opened_file, reserved_bits)
+
+
+def decode_iw_subswaths(filename: str, dtype: str = "complex128") -> List[Tuple[np.ndarray, pd.DataFrame]]:
+    """Decode the echoes of each sub-swath of an IW measurement file separately.
+
+    Args:
+        filename:   Path to the measurement data file.
+        dtype:      Output data type, either "complex64" or "complex128".
+
+    Returns:
+        A list of the decoded echoes and headers of each sub-swath, i.e. IW1,
+        IW2 and IW3 in that order. See Level0Decoder.decode_subswaths().
+    """
+    return Level0Decoder(filename).decode_subswaths(dtype)
+
+
+def decode_measurement(filename: str, dtype: str = "complex128") -> Tuple[np.ndarray, pd.DataFrame]:
+    """Decode all the radar echoes in a Level 0 measurement file.
+
+    Only echo packets are decoded, so noise, calibration and any trailing
+    packets using other BAQ modes are ignored. Echoes with fewer quads than
+    the longest echo are zero-padded.
+
+    Args:
+        filename:   Path to the measurement data file, e.g. the .dat file in a
+                    .SAFE directory.
+        dtype:      Output data type, either "complex64" or "complex128".
+
+    Returns:
+        The complex I/Q values of the echoes, one row per packet.
+        A DataFrame containing the headers of the echo packets, in the same order.
+    """
+    return Level0Decoder(filename).decode_file(cnst.ECHO_SIGNAL_TYPE, pad=True, dtype=dtype)
+
+
+def summarize_file(source: Union[str, bytes]) -> dict:
+    """Summarize the packets in a Level 0 file without decoding their user data.
+
+    Args:
+        source: Either the path to a Level 0 file, or the contents of one as a
+                bytes-like object.
+
+    Returns:
+        A dict containing the number of packets, the number of packets with
+        each BAQ mode, signal type and swath number, and the (minimum, maximum)
+        number of quads. Packets without a secondary header are only included
+        in the packet count.
+    """
+    counted_fields = (cnst.BAQ_MODE_FIELD_NAME, cnst.SIGNAL_TYPE_FIELD_NAME, cnst.SWATH_NUM_FIELD_NAME)
+    counts = {field: {} for field in counted_fields}
+    num_packets = 0
+    min_quads = None
+    max_quads = None
+
+    opened_file = open(source, 'rb') if isinstance(source, (str, os.PathLike)) else io.BytesIO(source)
+    with opened_file:
+        while True:
+            try:
+                this_header, _ = _read_single_packet(opened_file)
+            except NoMorePacketsException:
+                break
+            except TruncatedPacketException:
+                logging.warning(f"File ends part-way through packet {num_packets}, stopping")
+                break
+
+            num_packets += 1
+            if not this_header[cnst.SECONDARY_HEADER_FIELD_NAME]:
+                continue
+
+            for field in counted_fields:
+                counts[field][this_header[field]] = counts[field].get(this_header[field], 0) + 1
+            nq = this_header[cnst.NUM_QUADS_FIELD_NAME]
+            min_quads = nq if min_quads is None else min(min_quads, nq)
+            max_quads = nq if max_quads is None else max(max_quads, nq)
+
+    summary = {cnst.NUM_PACKETS_FIELD_NAME: num_packets}
+    summary.update(counts)
+    summary[cnst.NUM_QUADS_FIELD_NAME] = (min_quads, max_quads)
+    return summary
+
+
+def decode_file_to_npy(
+    input_path: str,
+    output_path: str,
+    num_quads: int,
+    dtype: str = "complex128",
+    respect_swap: bool = False
+) -> int:
+    """Decode the FDBAQ packets of a file straight into a .npy file on disk.
+
+    The input is read once to count the packets to decode, so the .npy header
+    can be written first, then again to decode each packet and append its row
+    to the output. Only one packet is held in memory at once, so files larger
+    than memory can be decoded, and the output can be opened with
+    numpy.load(output_path, mmap_mode='r'). Packets selected are the same as
+    FdbaqPacketIterator with num_quads given, so if the file ends part-way
+    through a packet, the output stops at the last complete packet. Packets
+    which fail to decode are written as rows of zeros, so the output always
+    holds the number of rows its header gives.
+
+    Args:
+        input_path:     Path to a Level 0 file.
+        output_path:    Path of the .npy file to write.
+        num_quads:      Number of quads of the packets to decode. Packets with
+                        other numbers of quads are skipped.
+        dtype:          Output data type, either "complex64" or "complex128".
+        respect_swap:   Exchange the I and Q channels of packets with the swap
+                        flag set.
+
+    Returns:
+        The number of rows written.
+    """
+    dtype = check_complex_dtype(dtype)
+
+    num_rows = 0
+    with open(input_path, 'rb') as opened_file:
+        for _ in _fdbaq_packets(opened_file, num_quads):
+            num_rows += 1
+
+    header = {
+        'descr': np.lib.format.dtype_to_descr(dtype),
+        'fortran_order': False,
+        'shape': (num_rows, 2 * num_quads),
+    }
+    row = np.zeros(2 * num_quads, dtype=dtype)
+    with open(input_path, 'rb') as opened_file, open(output_path, 'wb') as output_file:
+        np.lib.format.write_array_header_1_0(output_file, header)
+        for packet_counter, (this_header, packet_data_bytes) in enumerate(_fdbaq_packets(opened_file, num_quads)):
+            try:
+                baqmod = this_header[cnst.BAQ_MODE_FIELD_NAME]
+                swap = respect_swap and this_header[cnst.SWAP_FLAG_FIELD_NAME]
+                user_data_decoder(packet_data_bytes, baqmod, num_quads, swap).decode(out=row)
+            except Exception as e:
+                logging.error(f"Failed to process packet {packet_counter} with Space Packet Count {this_header[cnst.SPACE_PACKET_COUNT_FIELD_NAME]}\n{e}")
+                row[:] = 0
+            output_file.write(row.tobytes())
+
+    return num_rows
+
+
+def _fdbaq_packets(opened_file: BinaryIO, num_quads: Optional[int] = None):
+    """Read the FDBAQ packets with user data from an opened file, stopping at any truncated packet."""
+    while True:
+        try:
+            this_header, packet_data_bytes = _read_single_packet(opened_file)
+        except NoMorePacketsException:
+            return
+        except TruncatedPacketException:
+            logging.warning("File ends part-way through a packet, stopping")
+            return
+
+        if _is_fdbaq_packet(this_header, packet_data_bytes, num_quads):
+            yield this_header, packet_data_bytes
+
+
+def _is_fdbaq_packet(this_header: dict, packet_data_bytes: bytes, num_quads: Optional[int]) -> bool:
+    """Check if a packet holds FDBAQ user data, with the given number of quads if any.
+
+    A packet with user data and a reserved BAQ mode code raises an
+    InvalidBaqModeError rather than being skipped.
+    """
+    if not this_header[cnst.SECONDARY_HEADER_FIELD_NAME] or not len(packet_data_bytes):
+        return False
+    if num_quads is not None and this_header[cnst.NUM_QUADS_FIELD_NAME] != num_quads:
+        return False
+    return BaqMode.from_code(this_header[cnst.BAQ_MODE_FIELD_NAME]).is_fdbaq
+
+
+class FdbaqPacketIterator:
+    """Iterator which lazily decodes the FDBAQ packets in a file one at a time.
+
+    Only one packet is held in memory at once, so arbitrarily large files can
+    be processed in constant memory. Packets using other BAQ modes, or with no
+    user data, are skipped. If the file ends part-way through a packet,
+    iteration stops at the last complete packet.
+
+    Can be used as a context manager to close the file when done.
+    """
+
+    def __init__(self, source: Union[str, bytes], num_quads: Optional[int] = None, respect_swap: bool = False):
+        """
+        Args:
+            source:     Either the path to a Level 0 file, or the contents of
+                        one as a bytes-like object.
+            num_quads:  If supplied, only packets with this number of quads are
+                        decoded and all others are skipped.
+            respect_swap:   Exchange the I and Q channels of packets with the
+                            swap flag set.
+        """
+        if isinstance(source, (str, os.PathLike)):
+            self._file = open(source, 'rb')
+        else:
+            self._file = io.BytesIO(source)
+        self._respect_swap = respect_swap
+        self._packets = _fdbaq_packets(self._file, num_quads)
+
+    def __iter__(self):
+        return self
+
+    def __next__(self) -> np.ndarray:
+        try:
+            this_header, packet_data_bytes = next(self._packets)
+        except StopIteration:
+            self.close()
+            raise
+
+        nq = this_header[cnst.NUM_QUADS_FIELD_NAME]
+        baqmod = this_header[cnst.BAQ_MODE_FIELD_NAME]
+        swap = self._respect_swap and this_header[cnst.SWAP_FLAG_FIELD_NAME]
+        return user_data_decoder(packet_data_bytes, baqmod, nq, swap).decode()
+
+    def __enter__(self) -> "FdbaqPacketIterator":
+        return self
+
+    def __exit__(self, *args) -> None:
+        self.close()
+
+    def close(self) -> None:
+        """Close the underlying file."""
+        self._packets.close()
+        self._file.close()
+
+
+class StreamingHeaderParser:
+    """
+    Parser for packets arriving in chunks, e.g. from a live downlink.
+
+    Data is supplied with push() as it arrives. Partial packets are buffered
+    between calls, and each packet is only returned once all of its packet
+    data field has arrived. Packets too short to hold the secondary header
+    they claim to have are skipped with a warning.
+    """
+
+    def __init__(self):
+        self._buffer = bytearray()
+        # Offset of the start of the buffer from the start of the stream
+        self._stream_offset = 0
+
+    def push(self, data: bytes) -> List[Tuple[dict, Tuple[int, int], bytes]]:
+        """
+        Add the next chunk of data from the stream.
+
+        Args:
+            data:   The next bytes of the stream.
+
+        Returns:
+            A list with an entry for each packet completed by this chunk. Each
+            entry holds a dict of the packet's header fields, the offset and
+            length of its user data from the start of the stream, and the raw
+            bytes of its user data.
+        """
+        self._buffer += data
+        packets = []
+        position = 0
+        while len(self._buffer) - position >= 6:
+            this_header = hdrs.decode_primary_header(bytes(self._buffer[position:position+6]))
+            packet_end = position + 6 + this_header[cnst.PACKET_DATA_LEN_FIELD_NAME]
+            if packet_end > len(self._buffer):
+                break
+
+            user_data_start = position + 6
+            if this_header[cnst.SECONDARY_HEADER_FIELD_NAME]:
+                if packet_end < user_data_start + 62:
+                    logging.warning(f"Skipping packet at stream offset {self._stream_offset + position} too short to hold its secondary header")
+                    position = packet_end
+                    continue
+                this_header.update(hdrs.decode_secondary_header(bytes(self._buffer[user_data_start:user_data_start+62])))
+                user_data_start += 62
+
+            bounds = (self._stream_offset + user_data_start, packet_end - user_data_start)
+            packets.append((this_header, bounds, bytes(self._buffer[user_data_start:packet_end])))
+            position = packet_end
+
+        del self._buffer[:position]
+        self._stream_offset += position
+        return packets
+
+    @property
+    def buffered_bytes(self) -> int:
+        """Number of bytes of incomplete packets waiting for more data."""
+        return len(self._buffer)
+
+
+def _decode_headers(header_bytes_list: List[bytes], reserved_bits: bool = False) -> List[dict]:
+    """
+    Decode the primary and secondary headers of a list of packets.
+
+    Args:
+        header_bytes_list:  List of the first 68 bytes of each packet, or the
+                            whole packet if it is shorter than that.
+        reserved_bits:      Also record the unused bits of the secondary headers.
+
+    Returns:
+        A list of dicts of the header data fields for each packet.
+    """
+    output_row_list = []
+    for header_bytes in header_bytes_list:
+        output_dictionary_row = hdrs.decode_primary_header(header_bytes[:6])
+        user_data_len = output_dictionary_row[cnst.PACKET_DATA_LEN_FIELD_NAME]
+        if output_dictionary_row[cnst.SECONDARY_HEADER_FIELD_NAME]:
+            output_dictionary_row.update(hdrs.decode_secondary_header(header_bytes[6:68], reserved_bits))
+            user_data_len -= 62
+        output_dictionary_row[cnst.HAS_USER_DATA_FIELD_NAME] = user_data_len > 0
+        output_row_list.append(output_dictionary_row)
+    return output_row_list
+
+
+def _with_user_data(input_header: pd.DataFrame) -> pd.DataFrame:
+    """Drop the packets marked as having no user data, if the headers mark them."""
+    if cnst.HAS_USER_DATA_FIELD_NAME not in input_header:
+        return input_header
+    return input_header[input_header[cnst.HAS_USER_DATA_FIELD_NAME].astype(bool)]
+
+
+def _find_next_packet(opened_file: BinaryIO, start: int) -> Optional[int]:
+    """
+    Search the file for the next packet with a valid sync marker.
+
+    Args:
+        opened_file:    Sentinel-1 RAW file opened in 'rb' mode.
+        start:          Byte offset to start searching for a packet from.
+
+    Returns:
+        The byte offset of the start of the packet, or None if there are no
+        more sync markers in the file.
+    """
+    sync_bytes = cnst.SYNC_MARKER.to_bytes(4, 'big')
+
+    # The sync marker follows the 6-byte primary header and 6-byte datation service
+    position = start + 12
+    opened_file.seek(position)
+    tail = b''
+    while True:
+        chunk = opened_file.read(1 << 20)
+        if not chunk:
+            return None
+        search_buffer = tail + chunk
+        index = search_buffer.find(sync_bytes)
+        if index >= 0:
+            return position - len(tail) + index - 12
+        # Keep the end of this chunk, in case a marker spans two chunks
+        tail = search_buffer[-3:]
+        position += len(chunk)
+
+
+def _decode_packet_rows(
+    header_rows: List[dict],
+    packet_data: List[Union[bytes, memoryview]],
+    pad: bool,
+    dtype: str,
+    check_lengths: bool = False,
+    order: str = "C",
+    respect_swap: bool = False
+) -> Tuple[np.ndarray, pd.DataFrame]:
+    """
+    Decode the user data of a list of packets into one array.
+
+    Args:
+        header_rows:    List of the header fields of each packet.
+        packet_data:    List of the user data of each packet.
+        pad:            Zero-pad packets with fewer quads than the longest packet,
+                        rather than raising an error.
+        dtype:          Output data type, either "complex64" or "complex128".
+        check_lengths:  Add the number of bytes of each packet's user data left
+                        over after decoding to the headers. NaN for packets which
+                        failed to decode.
+        order:          Memory layout of the output, "C" for row-major or "F" for
+                        column-major.
+        respect_swap:   Exchange the I and Q channels of packets with the swap
+                        flag set.
+
+    Returns:
+        The complex I/Q values from the packets, one row per packet.
+        A DataFrame containing the headers of the packets.
+    """
+    dtype = check_complex_dtype(dtype)
+    headers = pd.DataFrame(header_rows)
+    if not len(headers):
+        return np.zeros([0, 0], dtype=dtype, order=order), headers
+
+    num_quads = headers[cnst.NUM_QUADS_FIELD_NAME].unique()
+    if not pad and not len(num_quads) == 1:
+        logging.error(f"Selected packets have too many number of quads {num_quads}")
+        raise Exception(f"Received {len(num_quads)} different number of quads {num_quads}, expected 1.")
+
+    output_data = np.zeros([len(headers), num_quads.max() * 2], dtype=dtype, order=order)
+    residuals = np.full(len(headers), np.nan)
+    for packet_counter, (this_header, packet_data_bytes) in enumerate(zip(header_rows, packet_data)):
+        try:
+            baqmod = this_header[cnst.BAQ_MODE_FIELD_NAME]
+            nq = this_header[cnst.NUM_QUADS_FIELD_NAME]
+            swap = respect_swap and this_header[cnst.SWAP_FLAG_FIELD_NAME]
+            data_decoder = user_data_decoder(packet_data_bytes, baqmod, nq, swap)
+            data_decoder.decode(out=output_data[packet_counter, :nq * 2])
+            residuals[packet_counter] = len(packet_data_bytes) - data_decoder.consumed_bytes
+        except Exception as e:
+            logging.error(f"Failed to process packet {packet_counter} with Space Packet Count {this_header[cnst.SPACE_PACKET_COUNT_FIELD_NAME]}\n{e}")
+            output_data[packet_counter, :] = 0
+
+    if check_lengths:
+        headers[cnst.USER_DATA_RESIDUAL_FIELD_NAME] = residuals
+
+    return output_data, headers
+
+
+def _decode_user_data(packets: List[tuple]) -> List[Optional[np.ndarray]]:
+    """
+    Decode the user data of a list of packets.
+
+    Args:
+        packets:    List of the user data, BAQ mode, number of quads and swap
+                    flag of each packet.
+
+    Returns:
+        A list of the complex I/Q samples of each packet, or None for any
+        packet which failed to decode.
+    """
+    output_rows = []
+    for packet_data_bytes, baqmod, nq, swap in packets:
+        try:
+            output_rows.append(user_data_decoder(packet_data_bytes, baqmod, nq, swap).decode())
+        except Exception as e:
+            logging.error(f"Failed to process packet with BAQ mode {baqmod} and {nq} quads\n{e}")
+            output_rows.append(None)
+    return output_rows
+
+
+def _read_packet_header(buffer: mmap.mmap, offset: int) -> Tuple[dict, int, int]:
+    """
+    Decode the headers of the packet at a byte offset of a buffer, without copying its user data.
+
+    Packets are checked in the same way as _read_single_packet.
+
+    Args:
+        buffer: Buffer holding the packets, e.g. a memory-mapped file.
+        offset: Byte offset of the start of the packet.
+
+    Returns:
+        A dict of the header data fields for this packet
+        The byte offset of the start of the packet's user data
+        The byte offset of the end of the packet, i.e. the start of the next
+    """
+    if offset >= len(buffer):
+        raise NoMorePacketsException()
+    if offset + 6 > len(buffer):
+        raise TruncatedPacketException(f"Unexpectedly hit EOF while trying to read packet primary header.")
+
+    output_dictionary_row = hdrs.decode_primary_header(buffer[offset:offset+6])
+    packet_end = offset + 6 + output_dictionary_row[cnst.PACKET_DATA_LEN_FIELD_NAME]
+    if packet_end > len(buffer):
+        raise TruncatedPacketException(f"Unexpectedly hit EOF while trying to read packet data field.")
+
+    if not output_dictionary_row[cnst.SECONDARY_HEADER_FIELD_NAME]:
+        return output_dictionary_row, offset + 6, packet_end
+
+    if packet_end < offset + 68 and packet_end == len(buffer):
+        raise TruncatedPacketException(f"Unexpectedly hit EOF while trying to read packet secondary header.")
+
+    output_dictionary_row.update(hdrs.decode_secondary_header(buffer[offset+6:min(offset+68, packet_end)]))
+    return output_dictionary_row, min(offset + 68, packet_end), packet_end
+
+
+def _read_single_packet(opened_file: BinaryIO, reserved_bits: bool = False) -> Tuple[dict, bytes]:
+    """
+    Read a single packet of data from the file.
+
+    Args:
+        opened_file:    Sentinel-1 RAW file opened in 'rb' mode with read
+                        position at the start of a packet
+        reserved_bits:  Also record the unused bits of the secondary header.
+
+    Returns:
+        A dict of the header data fields for this packet
+        The raw bytes of the user data payload for this packet
+    """
+    # PACKET PRIMARY HEADER (6 bytes)
+    # First check if we have reached the end of the file
+    data_buffer = opened_file.read(6)
+    if not data_buffer:
+        raise NoMorePacketsException()
+    if len(data_buffer) < 6:
+        raise TruncatedPacketException(f"Unexpectedly hit EOF while trying to read packet primary header.")
+
+    output_dictionary_row = hdrs.decode_primary_header(data_buffer)
+
+    # PACKET DATA FIELD (between 62 and 65534 bytes)
+    # First 62 bytes contain the PACKET SECONDARY HEADER
+    pkt_data_len = output_dictionary_row[cnst.PACKET_DATA_LEN_FIELD_NAME]
+    packet_data_buffer = opened_file.read(pkt_data_len)
+    if len(packet_data_buffer) < pkt_data_len:
+        raise TruncatedPacketException(f"Unexpectedly hit EOF while trying to read packet data field.")
 
-        output_dictionary_row = hdrs.decode_primary_header(data_buffer)
+    # Without a secondary header, the whole packet data field is user data
+    if not output_dictionary_row[cnst.SECONDARY_HEADER_FIELD_NAME]:
+        return output_dictionary_row, packet_data_buffer
 
-        # PACKET DATA FIELD (between 62 and 65534 bytes)
-        # First 62 bytes contain the PACKET SECONDARY HEADER
-        pkt_data_len = output_dictionary_row[cnst.PACKET_DATA_LEN_FIELD_NAME]
-        packet_data_buffer = opened_file.read(pkt_data_len)
-        if not packet_data_buffer:
-            raise Exception(f"Unexpectedly hit EOF while trying to read packet data field.")
+    # A final packet too short to hold the secondary header it claims to have
+    # is treated as truncated, so the packets before it can still be used
+    if len(packet_data_buffer) < 62:
+        if not opened_file.read(1):
+            raise TruncatedPacketException(f"Unexpectedly hit EOF while trying to read packet secondary header.")
+        opened_file.seek(-1, io.SEEK_CUR)
 
-        secondary_hdr = hdrs.decode_secondary_header(packet_data_buffer[:62])
-        output_dictionary_row.update(secondary_hdr)
+    secondary_hdr = hdrs.decode_secondary_header(packet_data_buffer[:62], reserved_bits)
+    output_dictionary_row.update(secondary_hdr)
 
-        # END OF SECONDARY HEADER.
-        # User data follows for bytes 62 ---> packet_data_length
-        output_bytes = packet_data_buffer[62:]
+    # END OF SECONDARY HEADER.
+    # User data follows for bytes 62 ---> packet_data_length
+    output_bytes = packet_data_buffer[62:]
 
-        return output_dictionary_row, output_bytes
+    return output_dictionary_row, output_bytes
 
 
 class NoMorePacketsException(Exception):
     """Exception raised when we run out of packets to read in a file"""
-    pass
\ No newline at end of file
+    pass
@@ -62,7 +62,7 @@ class Level0File:
         """
         return self.packet_metadata.loc[burst]
 
-    def get_burst_data(self, burst: int, try_load_from_file: bool = True) -> np.array:
+    def get_burst_data(self, burst: int, try_load_from_file: bool = True) -> np.ndarray:
         """
         Get an array of complex samples from the SAR instrument for a given burst.
         A burst is a set of consecutive space packets with constant number of samples.
@@ -0,0 +1,55 @@
+import numpy as np
+import pandas as pd
+
+from .utilities import _SPEC_FIELD_NAMES
+
+
+class PacketHeaders:
+    """Packet metadata with each field accessible as a named attribute.
+
+    Fields are named after their lower-cased mnemonics from the SAR Space
+    Protocol Data Unit specification, e.g. headers.baqmod, headers.nq and
+    headers.swath, and are returned as numpy arrays with one value per packet.
+    """
+
+    def __init__(self, metadata: pd.DataFrame):
+        """
+        Args:
+            metadata:   Packet metadata dataframe, as output by
+                        Level0Decoder.decode_metadata().
+        """
+        self._metadata = metadata
+
+    def __len__(self) -> int:
+        return len(self._metadata)
+
+    def __repr__(self) -> str:
+        return f"PacketHeaders({len(self)} packets)"
+
+    def __dir__(self):
+        return list(super().__dir__()) + [name for name in _FIELD_COLUMNS if _FIELD_COLUMNS[name] in self._metadata]
+
+    def __getattr__(self, name: str) -> np.ndarray:
+        # Only called for attributes not found the normal way, i.e. field names
+        if name.startswith("_"):
+            raise AttributeError(name)
+        column = _FIELD_COLUMNS.get(name)
+        if column is None or column not in self._metadata:
+            raise AttributeError(f"PacketHeaders has no field {name}")
+        return self._metadata[column].to_numpy()
+
+    def to_dict(self) -> dict:
+        """Get all the fields as a dict of numpy arrays, keyed by field name."""
+        return {
+            name: self._metadata[column].to_numpy()
+            for name, column in _FIELD_COLUMNS.items()
+            if column in self._metadata
+        }
+
+    def to_dataframe(self) -> pd.DataFrame:
+        """Get the underlying packet metadata dataframe."""
+        return self._metadata
+
+
+# Dataframe column for each attribute name
+_FIELD_COLUMNS = {mnemonic.lower(): column for column, mnemonic in _SPEC_FIELD_NAMES.items()}
@@ -0,0 +1,126 @@
+import logging
+import mmap
+import numpy as np
+import pandas as pd
+
+from typing import List, Tuple
+
+from . import constants as cnst
+from .exceptions import TruncatedPacketException
+from .l0decoder import NoMorePacketsException, _read_single_packet, _decode_packet_rows
+
+
+class Product:
+    """A Level 0 file held open for repeated decoding.
+
+    The file is memory-mapped and the headers of its packets are decoded once
+    when it's opened, so ranges of packets can then be decoded many times
+    without re-reading the file's headers. Use as a context manager, or call
+    close(), to release the file.
+    """
+
+    def __init__(self, filename: str):
+        """
+        Args:
+            filename:   Path to a Level 0 file.
+        """
+        self._file = open(filename, 'rb')
+        try:
+            self._mmap = mmap.mmap(self._file.fileno(), 0, access=mmap.ACCESS_READ)
+        except ValueError:
+            # Empty files can't be memory-mapped
+            self._mmap = None
+
+        self._header_rows = []
+        # Byte offset and length of the user data of each packet
+        self._user_data_bounds = []
+        while self._mmap is not None:
+            try:
+                this_header, packet_data_bytes = _read_single_packet(self._mmap)
+            except NoMorePacketsException:
+                break
+            except TruncatedPacketException:
+                logging.warning(f"File ends part-way through packet {len(self._header_rows)}, stopping")
+                break
+            self._header_rows.append(this_header)
+            self._user_data_bounds.append((self._mmap.tell() - len(packet_data_bytes), len(packet_data_bytes)))
+
+        self._headers = pd.DataFrame(self._header_rows)
+
+    def __enter__(self) -> "Product":
+        return self
+
+    def __exit__(self, exc_type, exc_value, traceback) -> None:
+        self.close()
+
+    def __len__(self) -> int:
+        return len(self._header_rows)
+
+    def close(self) -> None:
+        """Release the memory-mapped file."""
+        if self._mmap is not None:
+            self._mmap.close()
+            self._mmap = None
+        self._file.close()
+
+    def headers(self) -> pd.DataFrame:
+        """
+        Get the headers of every packet in the file.
+
+        Returns:
+            A Pandas Dataframe containing the decoded metadata, one row per packet.
+        """
+        return self._headers
+
+    def decode_range(self, start: int, end: int, pad: bool = False, dtype: str = "complex128") -> Tuple[np.ndarray, pd.DataFrame]:
+        """
+        Decode the user data of a range of packets.
+
+        Packets without a secondary header are skipped.
+
+        Args:
+            start:  Index of the first packet to decode, as in headers().
+            end:    Index one past the last packet to decode.
+            pad:    Zero-pad packets with fewer quads than the longest packet in
+                    the range, rather than raising an error.
+            dtype:  Output data type, either "complex64" or "complex128".
+
+        Returns:
+            The complex I/Q values from the packets, one row per packet.
+            A DataFrame containing the headers of the packets.
+        """
+        return self._decode_rows(range(*slice(start, end).indices(len(self))), pad, dtype)
+
+    def decode_swath(self, swath_number: int, dtype: str = "complex128") -> Tuple[np.ndarray, pd.DataFrame]:
+        """
+        Decode the echoes of one swath.
+
+        Packets with fewer quads than the longest in the swath are zero-padded.
+
+        Args:
+            swath_number:   Swath number of the echoes to decode.
+            dtype:          Output data type, either "complex64" or "complex128".
+
+        Returns:
+            The complex I/Q values of the swath's echoes, one row per packet.
+            A DataFrame containing the headers of the packets.
+        """
+        rows = [
+            i for i, this_header in enumerate(self._header_rows)
+            if this_header.get(cnst.SWATH_NUM_FIELD_NAME) == swath_number
+            and this_header.get(cnst.SIGNAL_TYPE_FIELD_NAME) == cnst.ECHO_SIGNAL_TYPE
+        ]
+        return self._decode_rows(rows, True, dtype)
+
+    def _decode_rows(self, rows: List[int], pad: bool, dtype: str) -> Tuple[np.ndarray, pd.DataFrame]:
+        """Decode the packets at the given indices which have a secondary header."""
+        if self._mmap is None and len(self):
+            raise ValueError("Product has been closed")
+
+        rows = [i for i in rows if self._header_rows[i][cnst.SECONDARY_HEADER_FIELD_NAME]]
+        packet_data = []
+        for i in rows:
+            offset, length = self._user_data_bounds[i]
+            packet_data.append(self._mmap[offset:offset+length])
+
+        return _decode_packet_rows([self._header_rows[i] for i in rows], packet_data, pad, dtype)
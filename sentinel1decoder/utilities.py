@@ -4,12 +4,19 @@ Created on Tue Jul 12 17:36:54 2022.
 
 @author: richa
 """
+import datetime
 import struct
 import numpy as np
 import pandas as pd
 
 from . import constants as cnst
 
+from typing import TYPE_CHECKING, Optional, Sequence, Tuple, Union
+
+if TYPE_CHECKING:
+    import pyarrow
+
+
 def range_dec_to_sample_rate(rgdec_code: int) -> float:
     """
     Convert range decimation code to sample rate.
@@ -21,31 +28,672 @@ def range_dec_to_sample_rate(rgdec_code: int) -> float:
         Sample rate for this range decimation code.
 
     """
-    if rgdec_code == 0:
-        return 3 * cnst.F_REF
-    elif rgdec_code == 1:
-        return (8/3) * cnst.F_REF
-    elif rgdec_code == 3:
-        return (20/9) * cnst.F_REF
-    elif rgdec_code == 4:
-        return (16/9) * cnst.F_REF
-    elif rgdec_code == 5:
-        return (3/2) * cnst.F_REF
-    elif rgdec_code == 6:
-        return (4/3) * cnst.F_REF
-    elif rgdec_code == 7:
-        return (2/3) * cnst.F_REF
-    elif rgdec_code == 8:
-        return (12/7) * cnst.F_REF
-    elif rgdec_code == 9:
-        return (5/4) * cnst.F_REF
-    elif rgdec_code == 10:
-        return (6/13) * cnst.F_REF
-    elif rgdec_code == 11:
-        return (16/11) * cnst.F_REF
-    else:
+    return range_decimation_info(rgdec_code)[cnst.RANGE_DEC_SAMPLE_RATE_FIELD_NAME]
+
+
+def range_decimation_info(rgdec_code: int) -> dict:
+    """
+    Look up the range decimation filter parameters for a range decimation code.
+
+    Refer to SAR Space Protocol Data Unit specification document.
+
+    Args:
+        rgdec_code: Range decimation code
+
+    Returns:
+        A dictionary containing the decimation ratio numerator and denominator,
+        the decimation filter length, and the resulting sample rate.
+    """
+    if rgdec_code not in _RANGE_DECIMATION_TABLE:
         raise Exception(f"Invalid range decimation code {rgdec_code} supplied - valid codes are 0-11")
 
+    numerator, denominator, filter_length = _RANGE_DECIMATION_TABLE[rgdec_code]
+    return {
+        cnst.RANGE_DEC_NUMERATOR_FIELD_NAME: numerator,
+        cnst.RANGE_DEC_DENOMINATOR_FIELD_NAME: denominator,
+        cnst.RANGE_DEC_FILTER_LENGTH_FIELD_NAME: filter_length,
+        cnst.RANGE_DEC_SAMPLE_RATE_FIELD_NAME: (numerator/denominator) * (4 * cnst.F_REF),
+    }
+
+
+# Decimation ratio numerator, denominator, and filter length for each range
+# decimation code. Code 2 is unused.
+_RANGE_DECIMATION_TABLE = {
+    0: (3, 4, 28),
+    1: (2, 3, 28),
+    3: (5, 9, 32),
+    4: (4, 9, 40),
+    5: (3, 8, 48),
+    6: (1, 3, 52),
+    7: (1, 6, 92),
+    8: (3, 7, 36),
+    9: (5, 16, 68),
+    10: (3, 26, 120),
+    11: (4, 11, 56),
+}
+
+
+def polarisation_str(pol_code: int) -> str:
+    """
+    Convert a polarisation code to a human-readable string.
+
+    The first letter gives the transmit polarisation, and any following letters
+    give the receive polarisation. Codes 3 and 7 receive both H and V.
+
+    Args:
+        pol_code:   Polarisation code
+
+    Returns:
+        The polarisation string for this code, e.g. "VH".
+    """
+    if pol_code not in _POLARISATION_TABLE:
+        raise Exception(f"Invalid polarisation code {pol_code} supplied - valid codes are 0-7")
+    return _POLARISATION_TABLE[pol_code]
+
+
+# Polarisation codes. Codes 0 and 4 are transmit only.
+_POLARISATION_TABLE = {
+    0: "H",
+    1: "HH",
+    2: "HV",
+    3: "HH+HV",
+    4: "V",
+    5: "VH",
+    6: "VV",
+    7: "VV+VH",
+}
+
+
+def test_mode_str(tstmod: int) -> str:
+    """
+    Convert a test mode code to a human-readable string.
+
+    Args:
+        tstmod: Test mode code from the packet's fixed ancillary data.
+
+    Returns:
+        The name of this test mode, e.g. "Default".
+    """
+    if tstmod not in _TEST_MODE_TABLE:
+        raise Exception(f"Invalid test mode code {tstmod} supplied - valid codes are 0 and 4-7")
+    return _TEST_MODE_TABLE[tstmod]
+
+
+# Test mode codes. Codes 1-3 are not used.
+_TEST_MODE_TABLE = {
+    0: "Default",
+    4: "Contingency - RXM Fully Operational",
+    5: "Contingency - RXM Partially Operational",
+    6: "Oper",
+    7: "Bypass",
+}
+
+
+def is_test_mode(tstmod: np.ndarray) -> np.ndarray:
+    """
+    Flag the packets which were not recorded in the default measurement mode.
+
+    Such packets hold test or contingency data, and should usually be excluded
+    from science processing.
+
+    Args:
+        tstmod: Test mode code of each packet.
+
+    Returns:
+        A boolean array, True for each packet in a test mode.
+    """
+    return np.asarray(tstmod) != 0
+
+
+def temperature_compensation_str(tcmp: int) -> str:
+    """
+    Convert a temperature compensation code to a human-readable string.
+
+    Args:
+        tcmp:   Temperature compensation code from the SAS SSB message.
+
+    Returns:
+        Whether compensation is on for the front-end (FE) and tile amplifiers
+        (TA), e.g. "FE On, TA Off".
+    """
+    if tcmp not in _TEMPERATURE_COMPENSATION_TABLE:
+        raise Exception(f"Invalid temperature compensation code {tcmp} supplied - valid codes are 0-3")
+    return _TEMPERATURE_COMPENSATION_TABLE[tcmp]
+
+
+_TEMPERATURE_COMPENSATION_TABLE = {
+    0: "FE Off, TA Off",
+    1: "FE On, TA Off",
+    2: "FE Off, TA On",
+    3: "FE On, TA On",
+}
+
+
+def is_calibration(sas_ssbflag: np.ndarray) -> np.ndarray:
+    """
+    Flag the packets recorded during calibration operation.
+
+    Uses the SAS SSB flag, which selects between the imaging and calibration
+    layouts of the SAS SSB message. The calibration mode field itself is left
+    as a raw code, as its values aren't named in the specification this
+    decoder follows.
+
+    Args:
+        sas_ssbflag:    SAS SSB flag of each packet.
+
+    Returns:
+        A boolean array, True for each calibration packet.
+    """
+    return np.asarray(sas_ssbflag) == 1
+
+
+def calibration_type_str(caltyp: int) -> str:
+    """
+    Convert a calibration type code to a human-readable string.
+
+    Args:
+        caltyp: Calibration type code, from the SAS SSB message of a
+                calibration packet.
+
+    Returns:
+        The name of this calibration type, e.g. "Tx Cal".
+    """
+    if caltyp not in _CALIBRATION_TYPE_TABLE:
+        raise Exception(f"Invalid calibration type code {caltyp} supplied - valid codes are 0-4 and 7")
+    return _CALIBRATION_TYPE_TABLE[caltyp]
+
+
+# Calibration type codes. Codes 5 and 6 are not used.
+_CALIBRATION_TYPE_TABLE = {
+    0: "Tx Cal",
+    1: "Rx Cal",
+    2: "EPDN Cal",
+    3: "TA Cal",
+    4: "APDN Cal",
+    7: "TxH Cal Iso",
+}
+
+
+def valid_sample_count(swl: np.ndarray, rgdec: np.ndarray, num_quads: np.ndarray) -> np.ndarray:
+    """
+    Find the number of complex samples per range line which fall within the receive window.
+
+    Packets may hold more samples than the sampling window, in which case the
+    samples after the end of the window are padding.
+
+    Args:
+        swl:        Sampling window length of each packet, in seconds.
+        rgdec:      Range decimation code of each packet.
+        num_quads:  Number of quads in each packet.
+
+    Returns:
+        An integer array of the number of valid complex samples in each packet,
+        at most twice the number of quads.
+    """
+    swl = np.asarray(swl, dtype=np.float64)
+    rgdec = np.asarray(rgdec)
+    num_quads = np.asarray(num_quads, dtype=np.int64)
+
+    sample_rates = {code: range_dec_to_sample_rate(code) for code in np.unique(rgdec).tolist()}
+    fs = np.vectorize(sample_rates.get, otypes=[np.float64])(rgdec) if rgdec.size else np.zeros(rgdec.shape)
+    window_samples = np.floor(swl * fs).astype(np.int64)
+    return np.minimum(window_samples, 2 * num_quads)
+
+
+def baq_block_layout(num_quads: int, block_size: int = cnst.BAQ_BLOCK_SIZE) -> Tuple[int, list]:
+    """
+    Find how the samples of each channel of a packet are split into BAQ blocks.
+
+    Every block holds block_size samples except the last, which holds the
+    remainder. Each block has one BRC and THIDX.
+
+    Args:
+        num_quads:  Number of quads in the packet.
+        block_size: Number of samples in each BAQ block.
+
+    Returns:
+        The number of BAQ blocks, and a list of the number of samples in each.
+    """
+    num_blocks = -(-num_quads // block_size)
+    block_sizes = [min(block_size, num_quads - block_index * block_size) for block_index in range(num_blocks)]
+    return num_blocks, block_sizes
+
+
+def pri_to_seconds(pri_count: np.ndarray) -> np.ndarray:
+    """
+    Convert the raw 24-bit PRI field to seconds.
+
+    Args:
+        pri_count:  PRI as a count of reference clock periods.
+
+    Returns:
+        The Pulse Repetition Interval in seconds.
+    """
+    return np.asarray(pri_count, dtype=np.float64) / cnst.F_REF
+
+
+def swst_to_seconds(swst_count: np.ndarray) -> np.ndarray:
+    """
+    Convert the raw 24-bit SWST field to seconds.
+
+    Args:
+        swst_count: Sampling Window Start Time as a count of reference clock periods.
+
+    Returns:
+        The Sampling Window Start Time in seconds.
+    """
+    return np.asarray(swst_count, dtype=np.float64) / cnst.F_REF
+
+
+def swl_to_seconds(swl_count: np.ndarray) -> np.ndarray:
+    """
+    Convert the raw 24-bit SWL field to seconds.
+
+    Args:
+        swl_count:  Sampling Window Length as a count of reference clock periods.
+
+    Returns:
+        The Sampling Window Length in seconds.
+    """
+    return np.asarray(swl_count, dtype=np.float64) / cnst.F_REF
+
+
+def slant_range_times(
+    rank: np.ndarray,
+    pri: np.ndarray,
+    swst: np.ndarray,
+    num_samples: np.ndarray,
+    fs: np.ndarray
+) -> Tuple[np.ndarray, np.ndarray]:
+    """
+    Calculate the two-way fast time of the first and last range sample of each packet.
+
+    The echo received in each sampling window was transmitted rank PRIs
+    earlier, so the fast time of the first sample is rank * PRI plus the SWST
+    and a fixed delay. Each later sample is one range sample period later.
+
+    Args:
+        rank:           Rank of each packet.
+        pri:            PRI of each packet, in seconds.
+        swst:           SWST of each packet, in seconds.
+        num_samples:    Number of complex samples in each packet, i.e. twice
+                        the number of quads.
+        fs:             Range sample rate in Hz.
+
+    Returns:
+        Arrays of the near and far range times of each packet in seconds.
+    """
+    rank = np.asarray(rank, dtype=np.float64)
+    pri = np.asarray(pri, dtype=np.float64)
+    swst = np.asarray(swst, dtype=np.float64)
+    num_samples = np.asarray(num_samples, dtype=np.float64)
+    fs = np.asarray(fs, dtype=np.float64)
+
+    near_range = rank * pri + swst + cnst.SWST_DELAY
+    far_range = near_range + (num_samples - 1) / fs
+    return near_range, far_range
+
+
+def align_by_swst(samples: np.ndarray, swst: np.ndarray, rgdec: np.ndarray) -> np.ndarray:
+    """
+    Shift range lines so that samples with the same fast time share a column.
+
+    Each row is delayed by the number of range samples between its SWST and
+    the earliest SWST in the block, and zero-padded either side. The output
+    is widened to fit the row with the latest SWST, so column 0 is the first
+    sample of the earliest sampling window.
+
+    Args:
+        samples:    2D array of complex samples, one row per packet.
+        swst:       SWST of each packet, in seconds.
+        rgdec:      Range decimation code of each packet. Must be the same for
+                    every packet, as rows sampled at different rates can't
+                    share a fast time grid.
+
+    Returns:
+        A 2D array of the aligned samples, with the same number of rows as the
+        input.
+    """
+    samples = np.asarray(samples)
+    swst = np.asarray(swst, dtype=np.float64)
+    rgdec_codes = np.unique(rgdec).tolist()
+    if samples.ndim != 2 or len(swst) != samples.shape[0]:
+        raise ValueError("samples must be 2D with one SWST per row")
+    if not samples.shape[0]:
+        return samples.copy()
+    if len(rgdec_codes) != 1:
+        raise ValueError(f"Range lines have different range decimation codes: {rgdec_codes}")
+
+    fs = range_dec_to_sample_rate(rgdec_codes[0])
+    offsets = np.rint((swst - swst.min()) * fs).astype(np.int64)
+
+    num_rows, num_samples = samples.shape
+    aligned = np.zeros((num_rows, num_samples + offsets.max()), dtype=samples.dtype)
+    columns = offsets[:, np.newaxis] + np.arange(num_samples)
+    aligned[np.arange(num_rows)[:, np.newaxis], columns] = samples
+    return aligned
+
+
+def generate_tx_replica(
+    txprr: float,
+    txpsf: float,
+    txpl: float,
+    fs: float,
+    num_samples: int,
+    window: Optional[str] = None
+) -> np.ndarray:
+    """
+    Generate a model of the transmitted linear FM pulse.
+
+    The Tx pulse parameters should be supplied as decoded in the packet metadata,
+    which already includes the sign and scaling of the raw header fields.
+
+    Args:
+        txprr:          Tx pulse ramp rate in Hz/s.
+        txpsf:          Tx pulse start frequency in Hz.
+        txpl:           Tx pulse length in seconds.
+        fs:             Range sample rate in Hz.
+        num_samples:    Length of the output array. Samples after the end of
+                        the pulse are zero.
+        window:         Optional window to apply across the pulse for sidelobe
+                        control, one of "rectangular", "hann" or "hamming".
+                        By default the pulse is unwindowed, matching the
+                        transmitted signal.
+
+    Returns:
+        A complex64 array containing the sampled chirp replica.
+    """
+    if window not in _REPLICA_WINDOWS:
+        raise ValueError(f"Unrecognized window {window}, expected one of {[w for w in _REPLICA_WINDOWS if w is not None]}")
+
+    num_pulse_samples = min(int(np.floor(txpl * fs)), num_samples)
+    t = np.arange(num_pulse_samples) / fs - txpl / 2
+
+    # Phase terms of the chirp, centred on the middle of the pulse
+    phi1 = txpsf + txprr * txpl / 2
+    phi2 = txprr / 2
+
+    replica = np.zeros(num_samples, dtype=np.complex64)
+    replica[:num_pulse_samples] = np.exp(2j * np.pi * (phi1 * t + phi2 * t**2))
+    replica[:num_pulse_samples] *= _REPLICA_WINDOWS[window](num_pulse_samples)
+    return replica
+
+
+_REPLICA_WINDOWS = {
+    None: np.ones,
+    "rectangular": np.ones,
+    "hann": np.hanning,
+    "hamming": np.hamming,
+}
+
+
+def datation_to_seconds(
+    coarse_time: np.ndarray,
+    fine_time: np.ndarray,
+    epoch: datetime.datetime = cnst.DATATION_DEFAULT_EPOCH,
+    leap_seconds: Union[None, float, Sequence[Tuple[datetime.datetime, float]]] = None
+) -> np.ndarray:
+    """
+    Convert packet datation fields to seconds since a reference epoch.
+
+    The coarse time counts whole seconds from the GPS epoch, and the fine time
+    holds the fractional part of the second as decoded from the header. By
+    default the output is on the spacecraft's GPS time scale, which runs ahead
+    of UTC by the leap seconds since the GPS epoch.
+
+    Args:
+        coarse_time:    Coarse time of each packet.
+        fine_time:      Fine time of each packet, in fractions of a second.
+        epoch:          Epoch to measure the output times from.
+        leap_seconds:   To output UTC instead, either a fixed GPS-UTC offset in
+                        seconds, or a table of (UTC date, offset) pairs giving
+                        the offset from each date on, such as
+                        constants.GPS_UTC_LEAP_SECONDS. Times before the first
+                        date in a table are left uncorrected.
+
+    Returns:
+        An array of packet times in seconds since the epoch.
+    """
+    epoch_offset = (cnst.GPS_EPOCH - epoch).total_seconds()
+    coarse_time = np.asarray(coarse_time, dtype=np.float64)
+    fine_time = np.asarray(fine_time, dtype=np.float64)
+    gps_seconds = coarse_time + fine_time
+
+    if leap_seconds is None:
+        utc_offset = 0.0
+    elif np.ndim(leap_seconds) == 0:
+        utc_offset = float(leap_seconds)
+    else:
+        table = sorted(leap_seconds)
+        # Each offset applies from its UTC date, which is later in GPS time
+        thresholds = np.array([(date - cnst.GPS_EPOCH).total_seconds() + offset for date, offset in table])
+        offsets = np.array([0.0] + [offset for _, offset in table])
+        utc_offset = offsets[np.searchsorted(thresholds, gps_seconds, side='right')]
+
+    return gps_seconds - utc_offset + epoch_offset
+
+
+def azimuth_time(
+    coarse_time: np.ndarray,
+    fine_time: np.ndarray,
+    pri: np.ndarray,
+    pri_count: np.ndarray,
+    epoch: datetime.datetime = cnst.DATATION_DEFAULT_EPOCH,
+    leap_seconds: Union[None, float, Sequence[Tuple[datetime.datetime, float]]] = None
+) -> np.ndarray:
+    """
+    Calculate a monotonic azimuth time for each packet.
+
+    The datation time of the first packet is used as the reference, and each
+    later packet's time is found by counting the PRIs elapsed since the packet
+    before it. The 32-bit PRI count is allowed to wrap around.
+
+    Args:
+        coarse_time:    Coarse time of each packet.
+        fine_time:      Fine time of each packet, in fractions of a second.
+        pri:            PRI of each packet, in seconds.
+        pri_count:      PRI count of each packet.
+        epoch:          Epoch to measure the output times from.
+        leap_seconds:   GPS-UTC offset or leap second table to output UTC
+                        times, as for datation_to_seconds().
+
+    Returns:
+        An array of packet azimuth times in seconds since the epoch.
+    """
+    pri = np.asarray(pri, dtype=np.float64)
+    pri_count = np.asarray(pri_count, dtype=np.int64)
+    if not len(pri_count):
+        return np.zeros(0)
+
+    start_time = datation_to_seconds(coarse_time[:1], fine_time[:1], epoch, leap_seconds)[0]
+    pri_steps = np.diff(pri_count) % (1 << 32)
+    elapsed = np.concatenate([[0.0], np.cumsum(pri_steps * pri[1:])])
+    return start_time + elapsed
+
+
+def beam_info(swath: np.ndarray, ebadr: np.ndarray, abadr: np.ndarray) -> pd.DataFrame:
+    """
+    Label the sub-swath and beam that each packet belongs to.
+
+    Sub-swaths are numbered from 1 in order of increasing swath number, so for
+    TOPSAR modes the echo packets of IW1, IW2 and IW3 are labelled 1, 2 and 3.
+    Only the swath numbers present in the input are counted, so pass packets
+    of a single signal type to avoid e.g. noise swaths being numbered too.
+
+    Args:
+        swath:  Swath number of each packet.
+        ebadr:  Elevation beam address of each packet.
+        abadr:  Azimuth beam address of each packet.
+
+    Returns:
+        A dataframe with one row per packet, containing the sub-swath index and
+        the elevation and azimuth beam addresses.
+    """
+    _, subswath_index = np.unique(np.asarray(swath), return_inverse=True)
+    return pd.DataFrame({
+        cnst.SUBSWATH_INDEX_FIELD_NAME: subswath_index + 1,
+        cnst.ELEVATION_BEAM_ADDRESS_FIELD_NAME: np.asarray(ebadr),
+        cnst.AZIMUTH_BEAM_ADDRESS_FIELD_NAME: np.asarray(abadr),
+    })
+
+
+def group_into_bursts(df: pd.DataFrame) -> pd.DataFrame:
+    """
+    Partition the packets in a file into bursts.
+
+    A new burst is started whenever the swath number or azimuth beam address
+    changes, or the space packet or PRI counters are discontinuous. Echo, noise and calibration
+    packets are kept within the same burst, and can be separated afterwards
+    using the signal type.
+
+    Args:
+        df: Pandas dataframe containing the packet header information from the file.
+
+    Returns:
+        A pandas dataframe with one row per burst, giving the positions of the
+        first and last packets in the burst, its swath number, and flags
+        indicating which signal types are present.
+    """
+    swath = df[cnst.SWATH_NUM_FIELD_NAME].to_numpy()
+    space_packet_count = df[cnst.SPACE_PACKET_COUNT_FIELD_NAME].to_numpy().astype(np.int64)
+    pri_count = df[cnst.PRI_COUNT_FIELD_NAME].to_numpy().astype(np.int64)
+    signal_type = df[cnst.SIGNAL_TYPE_FIELD_NAME].to_numpy()
+    az_beam_address = df[cnst.AZIMUTH_BEAM_ADDRESS_FIELD_NAME].to_numpy()
+
+    new_burst = np.ones(len(df), dtype=bool)
+    new_burst[1:] = (
+        (np.diff(swath) != 0)
+        | (np.diff(az_beam_address) != 0)
+        | (np.diff(space_packet_count) != 1)
+        | (np.diff(pri_count) != 1)
+    )
+    starts = np.flatnonzero(new_burst)
+    stops = np.append(starts[1:], len(df)) - 1
+
+    output_dict_list = []
+    for start, stop in zip(starts, stops):
+        burst_signal_types = signal_type[start:stop+1]
+        output_dict_list.append({
+            cnst.BURST_START_FIELD_NAME: start,
+            cnst.BURST_STOP_FIELD_NAME: stop,
+            cnst.SWATH_NUM_FIELD_NAME: swath[start],
+            cnst.BURST_HAS_ECHO_FIELD_NAME: np.any(burst_signal_types == cnst.ECHO_SIGNAL_TYPE),
+            cnst.BURST_HAS_NOISE_FIELD_NAME: np.any(burst_signal_types == cnst.NOISE_SIGNAL_TYPE),
+            cnst.BURST_HAS_CAL_FIELD_NAME: np.any(np.isin(burst_signal_types, cnst.CAL_SIGNAL_TYPES)),
+        })
+
+    out_df = pd.DataFrame(output_dict_list)
+    out_df.index = pd.RangeIndex(1, len(out_df) + 1, name=cnst.BURST_NUM_FIELD_NAME)
+    return out_df
+
+
+def swap_flag_mismatches(swap: np.ndarray, burst: np.ndarray) -> np.ndarray:
+    """
+    Flag packets whose swap flag differs from the rest of their burst.
+
+    The swap flag should be constant within a burst, so a packet which
+    disagrees with its burst's most common value suggests a bit error or
+    loss of synchronisation.
+
+    Args:
+        swap:   Swap flag of each packet.
+        burst:  Burst number of each packet, e.g. from the index of
+                Level0File.packet_metadata.
+
+    Returns:
+        A boolean array, True for each packet whose swap flag differs from the
+        dominant value in its burst.
+    """
+    swap = np.asarray(swap).astype(bool)
+    burst = np.asarray(burst)
+    if not swap.shape == burst.shape:
+        raise ValueError(f"Mismatched shapes of swap flags {swap.shape} and burst numbers {burst.shape}")
+
+    _, burst_index = np.unique(burst, return_inverse=True)
+    burst_index = burst_index.reshape(-1)
+    num_swapped = np.bincount(burst_index, weights=swap)
+    num_packets = np.bincount(burst_index)
+    # Ties are resolved in favour of the unswapped value
+    dominant = num_swapped > num_packets / 2
+    return swap != dominant[burst_index]
+
+
+def headers_to_arrow(df: pd.DataFrame, spec_names: bool = True) -> "pyarrow.RecordBatch":
+    """
+    Convert a packet metadata dataframe into an Arrow RecordBatch.
+
+    Requires the optional pyarrow dependency. Numeric columns are passed to
+    Arrow without copying where possible, and NaN values, e.g. the secondary
+    header fields of packets without a secondary header, become nulls.
+
+    Args:
+        df:         Packet metadata dataframe, as output by decode_metadata().
+        spec_names: Rename the columns to the field mnemonics used in the SAR
+                    Space Protocol Data Unit specification.
+
+    Returns:
+        A pyarrow RecordBatch with one row per packet.
+    """
+    try:
+        import pyarrow as pa
+    except ImportError:
+        raise ImportError("pyarrow is required for Arrow export. Install it with pip install sentinel1decoder[arrow]")
+
+    if spec_names:
+        df = df.rename(columns=_SPEC_FIELD_NAMES)
+    return pa.RecordBatch.from_pandas(df, preserve_index=False)
+
+
+# Field mnemonics used in the SAR Space Protocol Data Unit specification
+_SPEC_FIELD_NAMES = {
+    cnst.PACKET_VER_NUM_FIELD_NAME: "PVN",
+    cnst.PACKET_TYPE_FIELD_NAME: "PT",
+    cnst.SECONDARY_HEADER_FIELD_NAME: "SHF",
+    cnst.PID_FIELD_NAME: "PID",
+    cnst.PCAT_FIELD_NAME: "PCAT",
+    cnst.SEQUENCE_FLAGS_FIELD_NAME: "SF",
+    cnst.PACKET_SEQUENCE_COUNT_FIELD_NAME: "PSC",
+    cnst.PACKET_DATA_LEN_FIELD_NAME: "PDL",
+    cnst.COARSE_TIME_FIELD_NAME: "COARSE_TIME",
+    cnst.FINE_TIME_FIELD_NAME: "FINE_TIME",
+    cnst.SYNC_FIELD_NAME: "SYNC",
+    cnst.SYNC_VALID_FIELD_NAME: "SYNC_VALID",
+    cnst.DATA_TAKE_ID_FIELD_NAME: "DTID",
+    cnst.ECC_NUM_FIELD_NAME: "ECC",
+    cnst.TEST_MODE_FIELD_NAME: "TSTMOD",
+    cnst.RX_CHAN_ID_FIELD_NAME: "RXCHID",
+    cnst.INSTRUMENT_CONFIG_ID_FIELD_NAME: "ICID",
+    cnst.SUBCOM_ANC_DATA_WORD_INDEX_FIELD_NAME: "ADWIDX",
+    cnst.SUBCOM_ANC_DATA_WORD_FIELD_NAME: "ADW",
+    cnst.SPACE_PACKET_COUNT_FIELD_NAME: "SPCT",
+    cnst.PRI_COUNT_FIELD_NAME: "PRICT",
+    cnst.ERROR_FLAG_FIELD_NAME: "ERRFLG",
+    cnst.BAQ_MODE_FIELD_NAME: "BAQMOD",
+    cnst.BAQ_BLOCK_LEN_FIELD_NAME: "BAQBL",
+    cnst.RANGE_DEC_FIELD_NAME: "RGDEC",
+    cnst.RX_GAIN_FIELD_NAME: "RXG",
+    cnst.TX_RAMP_RATE_FIELD_NAME: "TXPRR",
+    cnst.TX_PULSE_START_FREQ_FIELD_NAME: "TXPSF",
+    cnst.TX_PULSE_LEN_FIELD_NAME: "TXPL",
+    cnst.RANK_FIELD_NAME: "RANK",
+    cnst.PRI_FIELD_NAME: "PRI",
+    cnst.SWST_FIELD_NAME: "SWST",
+    cnst.SWL_FIELD_NAME: "SWL",
+    cnst.SAS_SSB_FLAG_FIELD_NAME: "SSBFLAG",
+    cnst.POLARIZATION_FIELD_NAME: "POL",
+    cnst.TEMP_COMP_FIELD_NAME: "TCMP",
+    cnst.ELEVATION_BEAM_ADDRESS_FIELD_NAME: "EBADR",
+    cnst.AZIMUTH_BEAM_ADDRESS_FIELD_NAME: "ABADR",
+    cnst.CAL_TYPE_FIELD_NAME: "CALTYP",
+    cnst.CAL_BEAM_ADDRESS_FIELD_NAME: "CBADR",
+    cnst.CAL_MODE_FIELD_NAME: "CALMOD",
+    cnst.TX_PULSE_NUM_FIELD_NAME: "TXPNO",
+    cnst.SIGNAL_TYPE_FIELD_NAME: "SIGTYP",
+    cnst.SWAP_FLAG_FIELD_NAME: "SWAP",
+    cnst.SWATH_NUM_FIELD_NAME: "SWATH",
+    cnst.NUM_QUADS_FIELD_NAME: "NQ",
+}
+
 
 def read_subcommed_data(df: pd.DataFrame) -> pd.DataFrame:
     """
@@ -57,85 +705,122 @@ def read_subcommed_data(df: pd.DataFrame) -> pd.DataFrame:
     Returns:
         A pandas dataframe containing the decoded sub-commutated acilliary data words.
     """
-    if df.index.nlevels > 1:
-        df = df.droplevel(cnst.BURST_NUM_FIELD_NAME)
-
     index_col = cnst.SUBCOM_ANC_DATA_WORD_INDEX_FIELD_NAME
     data_col = cnst.SUBCOM_ANC_DATA_WORD_FIELD_NAME
-    start_indices = df.index[df[index_col] == 1]
+    out_df = reconstruct_ephemeris(df[index_col].to_numpy(), df[data_col].to_numpy())
+    return out_df.reset_index(drop=True)
+
+
+def reconstruct_ephemeris(adwidx: np.ndarray, adw: np.ndarray) -> pd.DataFrame:
+    """
+    Reassemble the sub-commutated ancillary data words into ephemeris records.
+
+    A full set of ancillary data is spread over 64 consecutive packets, with
+    word indices running from 1 to 64. Cycles which start part-way through
+    the input or are truncated at the end of it are skipped.
+
+    Args:
+        adwidx: Sub-commutated ancillary data word index of each packet.
+        adw:    Sub-commutated ancillary data word of each packet.
+
+    Returns:
+        A pandas dataframe of the decoded position, velocity and attitude
+        data, indexed by the position of the first packet of each cycle.
+    """
+    adwidx = np.asarray(adwidx)
+    adw = np.asarray(adw)
+    if not len(adwidx) == len(adw):
+        raise Exception(f"Mismatched lengths of word index ({len(adwidx)}) and data word ({len(adw)}) arrays")
+
+    start_indices = np.flatnonzero(adwidx == 1)
+    expected_indices = np.arange(1, 65)
     output_dict_list = []
+    cycle_starts = []
 
-    dbl_type = np.dtype(np.float64).newbyteorder('>')
-    sgl_type = np.dtype(np.float32).newbyteorder('>')
     for i in start_indices:
-
         # check our index is followed by a continuous block of 64
-        if len(df) - i >= 64:
-            if all(df.loc[i:i+63][index_col] == list(range(1, 65))):
-                d = df.loc[i:i+63][data_col].tolist()
-                x_bytes = struct.pack('>HHHH', d[0], d[1], d[2], d[3])
-                y_bytes = struct.pack('>HHHH', d[4], d[5], d[6], d[7])
-                z_bytes = struct.pack('>HHHH', d[8], d[9], d[10], d[11])
-                x = np.frombuffer(x_bytes, dtype=dbl_type)[0]
-                y = np.frombuffer(y_bytes, dtype=dbl_type)[0]
-                z = np.frombuffer(z_bytes, dtype=dbl_type)[0]
-
-                x_vel_bytes = struct.pack('>HH', d[12], d[13])
-                y_vel_bytes = struct.pack('>HH', d[14], d[15])
-                z_vel_bytes = struct.pack('>HH', d[16], d[17])
-                x_vel = np.frombuffer(x_vel_bytes, dtype=sgl_type)[0]
-                y_vel = np.frombuffer(y_vel_bytes, dtype=sgl_type)[0]
-                z_vel = np.frombuffer(z_vel_bytes, dtype=sgl_type)[0]
-
-                pvt_t1 = d[18] * 2**24
-                pvt_t2 = (d[19] * 2**8)
-                pvt_t3 = (d[20] * 2**-8)
-                pvt_t4 = (d[21] * 2**-24)
-                pvt_t = pvt_t1 + pvt_t2 + pvt_t3 + pvt_t4
-
-                output_dictionary = {
-                    cnst.X_POS_FIELD_NAME: x,
-                    cnst.Y_POS_FIELD_NAME: y,
-                    cnst.Z_POS_FIELD_NAME: z,
-                    cnst.X_VEL_FIELD_NAME: x_vel,
-                    cnst.Y_VEL_FIELD_NAME: y_vel,
-                    cnst.Z_VEL_FIELD_NAME: z_vel,
-                    cnst.POD_SOLN_DATA_TIMESTAMP_FIELD_NAME: pvt_t
-                }
-
-                q0_bytes = struct.pack('>HH', d[22], d[23])
-                q1_bytes = struct.pack('>HH', d[24], d[25])
-                q2_bytes = struct.pack('>HH', d[26], d[27])
-                q3_bytes = struct.pack('>HH', d[28], d[29])
-                q0 = np.frombuffer(q0_bytes, dtype=sgl_type)[0]
-                q1 = np.frombuffer(q1_bytes, dtype=sgl_type)[0]
-                q2 = np.frombuffer(q2_bytes, dtype=sgl_type)[0]
-                q3 = np.frombuffer(q3_bytes, dtype=sgl_type)[0]
-
-                x_ang_rate_bytes = struct.pack('>HH', d[30], d[31])
-                y_ang_rate_bytes = struct.pack('>HH', d[32], d[33])
-                z_ang_rate_bytes = struct.pack('>HH', d[34], d[35])
-                x_ang_rate = np.frombuffer(x_ang_rate_bytes, dtype=sgl_type)[0]
-                y_ang_rate = np.frombuffer(y_ang_rate_bytes, dtype=sgl_type)[0]
-                z_ang_rate = np.frombuffer(z_ang_rate_bytes, dtype=sgl_type)[0]
-
-                att_t1 = d[36] * 2**24
-                att_t2 = (d[37] * 2**8)
-                att_t3 = (d[38] * 2**-8)
-                att_t4 = (d[39] * 2**-24)
-                att_t = att_t1 + att_t2 + att_t3 + att_t4
-
-                output_dictionary.update({
-                    cnst.Q0_FIELD_NAME: q0,
-                    cnst.Q1_FIELD_NAME: q1,
-                    cnst.Q2_FIELD_NAME: q2,
-                    cnst.Q3_FIELD_NAME: q3,
-                    cnst.X_ANG_RATE_FIELD_NAME: x_ang_rate,
-                    cnst.Y_ANG_RATE_FIELD_NAME: y_ang_rate,
-                    cnst.Z_ANG_RATE_FIELD_NAME: z_ang_rate,
-                    cnst.ATTITUDE_DATA_TIMESTAMP_FIELD_NAME: att_t
-                })
-
-                output_dict_list.append(output_dictionary)
-    out_df = pd.DataFrame(output_dict_list)
+        if len(adwidx) - i >= 64 and np.array_equal(adwidx[i:i+64], expected_indices):
+            output_dict_list.append(_decode_subcom_cycle([int(w) for w in adw[i:i+64]]))
+            cycle_starts.append(i)
+
+    out_df = pd.DataFrame(output_dict_list, index=pd.Index(cycle_starts, name=cnst.SUBCOM_CYCLE_START_FIELD_NAME))
     return out_df
+
+
+def _decode_subcom_cycle(d: list) -> dict:
+    """
+    Decode a single complete cycle of 64 sub-commutated data words.
+
+    Args:
+        d: List of the 64 data words in the cycle.
+
+    Returns:
+        A dictionary of the decoded ephemeris fields.
+    """
+    dbl_type = np.dtype(np.float64).newbyteorder('>')
+    sgl_type = np.dtype(np.float32).newbyteorder('>')
+
+    x_bytes = struct.pack('>HHHH', d[0], d[1], d[2], d[3])
+    y_bytes = struct.pack('>HHHH', d[4], d[5], d[6], d[7])
+    z_bytes = struct.pack('>HHHH', d[8], d[9], d[10], d[11])
+    x = np.frombuffer(x_bytes, dtype=dbl_type)[0]
+    y = np.frombuffer(y_bytes, dtype=dbl_type)[0]
+    z = np.frombuffer(z_bytes, dtype=dbl_type)[0]
+
+    x_vel_bytes = struct.pack('>HH', d[12], d[13])
+    y_vel_bytes = struct.pack('>HH', d[14], d[15])
+    z_vel_bytes = struct.pack('>HH', d[16], d[17])
+    x_vel = np.frombuffer(x_vel_bytes, dtype=sgl_type)[0]
+    y_vel = np.frombuffer(y_vel_bytes, dtype=sgl_type)[0]
+    z_vel = np.frombuffer(z_vel_bytes, dtype=sgl_type)[0]
+
+    pvt_t1 = d[18] * 2**24
+    pvt_t2 = (d[19] * 2**8)
+    pvt_t3 = (d[20] * 2**-8)
+    pvt_t4 = (d[21] * 2**-24)
+    pvt_t = pvt_t1 + pvt_t2 + pvt_t3 + pvt_t4
+
+    output_dictionary = {
+        cnst.X_POS_FIELD_NAME: x,
+        cnst.Y_POS_FIELD_NAME: y,
+        cnst.Z_POS_FIELD_NAME: z,
+        cnst.X_VEL_FIELD_NAME: x_vel,
+        cnst.Y_VEL_FIELD_NAME: y_vel,
+        cnst.Z_VEL_FIELD_NAME: z_vel,
+        cnst.POD_SOLN_DATA_TIMESTAMP_FIELD_NAME: pvt_t
+    }
+
+    q0_bytes = struct.pack('>HH', d[22], d[23])
+    q1_bytes = struct.pack('>HH', d[24], d[25])
+    q2_bytes = struct.pack('>HH', d[26], d[27])
+    q3_bytes = struct.pack('>HH', d[28], d[29])
+    q0 = np.frombuffer(q0_bytes, dtype=sgl_type)[0]
+    q1 = np.frombuffer(q1_bytes, dtype=sgl_type)[0]
+    q2 = np.frombuffer(q2_bytes, dtype=sgl_type)[0]
+    q3 = np.frombuffer(q3_bytes, dtype=sgl_type)[0]
+
+    x_ang_rate_bytes = struct.pack('>HH', d[30], d[31])
+    y_ang_rate_bytes = struct.pack('>HH', d[32], d[33])
+    z_ang_rate_bytes = struct.pack('>HH', d[34], d[35])
+    x_ang_rate = np.frombuffer(x_ang_rate_bytes, dtype=sgl_type)[0]
+    y_ang_rate = np.frombuffer(y_ang_rate_bytes, dtype=sgl_type)[0]
+    z_ang_rate = np.frombuffer(z_ang_rate_bytes, dtype=sgl_type)[0]
+
+    att_t1 = d[36] * 2**24
+    att_t2 = (d[37] * 2**8)
+    att_t3 = (d[38] * 2**-8)
+    att_t4 = (d[39] * 2**-24)
+    att_t = att_t1 + att_t2 + att_t3 + att_t4
+
+    output_dictionary.update({
+        cnst.Q0_FIELD_NAME: q0,
+        cnst.Q1_FIELD_NAME: q1,
+        cnst.Q2_FIELD_NAME: q2,
+        cnst.Q3_FIELD_NAME: q3,
+        cnst.X_ANG_RATE_FIELD_NAME: x_ang_rate,
+        cnst.Y_ANG_RATE_FIELD_NAME: y_ang_rate,
+        cnst.Z_ANG_RATE_FIELD_NAME: z_ang_rate,
+        cnst.ATTITUDE_DATA_TIMESTAMP_FIELD_NAME: att_t
+    })
+
+    return output_dictionary
@@ -1,4 +1,5 @@
-from distutils.core import setup
+# setuptools rather than distutils, which ignores python_requires
+from setuptools import setup
 
 setup(
   name='sentinel1decoder',
@@ -6,7 +7,10 @@ setup(
   author='Rich Hall',
   author_email='richardhall434@gmail.com',
   packages=['sentinel1decoder',],
+  package_data={'sentinel1decoder': ['py.typed']},
+  python_requires='>=3.9',
   install_requires=['numpy', 'pandas'],
+  extras_require={'arrow': ['pyarrow']},
   version='0.1',
   license='GPL-3.0',
   description='A python decoder for ESA Sentinel-1 Level0 files',
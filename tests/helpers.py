@@ -0,0 +1,50 @@
+import math
+
+from sentinel1decoder._fdbaq_decoder import huffman_codes, _TREES
+from sentinel1decoder._fdbaq_encoder import encode_fdbaq_packet
+
+import sentinel1decoder.constants as cnst
+
+# Largest magnitude code each BRC can encode
+MAX_MCODES = tuple(max(huffman_codes(tree)) for tree in _TREES)
+
+
+def fdbaq_codes(num_quads, brcs, seed=0):
+    # Magnitude codes cycling through every code each block's BRC allows, and
+    # a pattern of signs, both different in each channel
+    mcodes = [
+        [(n * (2 * channel + 1) + seed) % (MAX_MCODES[brcs[n // cnst.BAQ_BLOCK_SIZE]] + 1) for n in range(num_quads)]
+        for channel in range(4)
+    ]
+    signs = [[(n + channel + seed) % 3 == 0 for n in range(num_quads)] for channel in range(4)]
+    return mcodes, signs
+
+
+def fdbaq_packet(num_quads, brcs=None, thidxs=None, seed=0):
+    # Synthetic FDBAQ user data, along with the sample codes encoded in it.
+    # BRCs and THIDXs default to 0 for every block.
+    num_blocks = math.ceil(num_quads / cnst.BAQ_BLOCK_SIZE)
+    brcs = [0] * num_blocks if brcs is None else brcs
+    thidxs = [0] * num_blocks if thidxs is None else thidxs
+    mcodes, signs = fdbaq_codes(num_quads, brcs, seed)
+    return encode_fdbaq_packet(mcodes, signs, brcs, thidxs), mcodes, signs
+
+
+def packet_without_secondary_header(packet_sequence_count, user_data):
+    # Primary header with the secondary header flag clear, followed directly
+    # by the user data
+    header = bytes([0x00, 0x00]) + packet_sequence_count.to_bytes(2, "big") + (len(user_data) - 1).to_bytes(2, "big")
+    return header + user_data
+
+
+def echo_packet(space_packet_count, num_quads, user_data, baq_mode=12, signal_type=cnst.ECHO_SIGNAL_TYPE, swap=0, sync=cnst.SYNC_MARKER):
+    # Packet with a minimal secondary header, FDBAQ echo data by default
+    secondary_header = bytearray(62)
+    secondary_header[6:10] = sync.to_bytes(4, "big")
+    secondary_header[23:27] = space_packet_count.to_bytes(4, "big")
+    secondary_header[31] = baq_mode
+    secondary_header[57] = (signal_type << 4) | swap
+    secondary_header[59:61] = num_quads.to_bytes(2, "big")
+    user_data = user_data + bytes(-len(user_data) % 4)
+    packet_data = bytes(secondary_header) + user_data
+    return bytes([0x08, 0x00, 0xc0, 0x00]) + (len(packet_data) - 1).to_bytes(2, "big") + packet_data
@@ -0,0 +1,112 @@
+import pytest
+
+from sentinel1decoder._fdbaq_decoder import FDBAQDecoder, _TREES, _build_lookup_table, huffman_table_fingerprint
+from sentinel1decoder._fdbaq_encoder import encode_fdbaq_packet
+from sentinel1decoder.exceptions import BrcMismatchError, InvalidHuffmanCodeError, UnexpectedEofError
+
+from .helpers import fdbaq_codes, fdbaq_packet
+
+
+def test_lookup_tables_are_complete():
+    # A complete prefix-free code fills every entry of the lookup table, and
+    # each BRC uses every magnitude code from zero up to its largest
+    for tree in _TREES:
+        num_bits, table = _build_lookup_table(tree)
+        assert len(table) == 1 << num_bits
+        assert all(entry is not None for entry in table)
+        mcodes = {scode.mcode for scode, _ in table}
+        assert mcodes == set(range(max(mcodes) + 1))
+
+
+def test_huffman_table_fingerprints():
+    # Pinned so any change to the Huffman trees or table layout is noticed
+    assert [huffman_table_fingerprint(brc) for brc in range(len(_TREES))] == [
+        "a1951eb176c0532fc331ca4af9a7a9bc",
+        "0a3f11ae0c2e295787c047571d04d6c7",
+        "8434b494645ea508f3add360701d376f",
+        "392bed36398856991ab61e617b332b0e",
+        "b51e89bc9f76e8956a05d251b0cdb223",
+    ]
+
+
+def test_lookup_table_rejects_duplicate_codes():
+    with pytest.raises(ValueError):
+        _build_lookup_table((0, (1, 1)))
+
+
+def test_encode_decode_round_trip():
+    # Enough quads to span several blocks, with a partial final block
+    num_quads = 300
+    brcs = [0, 3, 4]
+    thidxs = [2, 10, 200]
+    data, mcodes, signs = fdbaq_packet(num_quads, brcs, thidxs)
+    decoder = FDBAQDecoder(data, num_quads)
+
+    assert decoder.get_brcs == brcs
+    assert decoder.get_thidxs == thidxs
+    channels = (decoder.get_s_ie, decoder.get_s_io, decoder.get_s_qe, decoder.get_s_qo)
+    for channel, scodes in enumerate(channels):
+        assert [s.mcode for s in scodes] == mcodes[channel]
+        assert [bool(s.sign) for s in scodes] == signs[channel]
+
+
+def test_decode_channel_order():
+    num_quads = 130
+    brcs = [2, 1]
+    thidxs = [5, 6]
+    mcodes, signs = fdbaq_codes(num_quads, brcs)
+
+    # Store the Q channels first, with the BRCs and THIDXs moving with them
+    data = encode_fdbaq_packet(mcodes[2:] + mcodes[:2], signs[2:] + signs[:2], brcs, thidxs)
+    decoder = FDBAQDecoder(data, num_quads, channel_order=("QE", "QO", "IE", "IO"))
+
+    assert decoder.get_brcs == brcs
+    assert decoder.get_thidxs == thidxs
+    channels = (decoder.get_s_ie, decoder.get_s_io, decoder.get_s_qe, decoder.get_s_qo)
+    for channel, scodes in enumerate(channels):
+        assert [s.mcode for s in scodes] == mcodes[channel]
+
+    with pytest.raises(ValueError):
+        FDBAQDecoder(data, num_quads, channel_order=("IE", "IE", "QE", "QO"))
+
+
+def test_decode_channel_spans():
+    num_quads = 130
+    data, mcodes, _ = fdbaq_packet(num_quads, brcs=[2, 1], thidxs=[5, 6])
+    end_bytes = FDBAQDecoder(data, num_quads).get_channel_end_bytes
+    spans = [end - start for start, end in zip([0] + end_bytes[:3], end_bytes)]
+
+    decoder = FDBAQDecoder(data, num_quads, channel_spans=spans)
+    assert decoder.get_channel_span_matches == [True] * 4
+    assert [s.mcode for s in decoder.get_s_qo] == mcodes[3]
+
+    spans[1] -= 2
+    with pytest.raises(UnexpectedEofError) as excinfo:
+        FDBAQDecoder(data, num_quads, channel_spans=spans)
+    assert excinfo.value.channel == "IO"
+
+    with pytest.raises(ValueError):
+        FDBAQDecoder(data, num_quads, channel_spans=spans[:3])
+
+
+def test_strict_decode_checks_channels_match_brcs():
+    num_quads = 20
+    brc_one, _, _ = fdbaq_packet(num_quads, brcs=[1])
+    brc_four, _, _ = fdbaq_packet(num_quads, brcs=[4])
+    FDBAQDecoder(brc_four, num_quads, strict=True)
+
+    # The first channel's BRC doesn't match how the other channels were encoded
+    first_end = FDBAQDecoder(brc_one, num_quads).get_channel_end_bytes[0]
+    other_start = FDBAQDecoder(brc_four, num_quads).get_channel_end_bytes[0]
+    mismatched = brc_one[:first_end] + brc_four[other_start:]
+    FDBAQDecoder(mismatched, num_quads)
+    with pytest.raises(BrcMismatchError) as excinfo:
+        FDBAQDecoder(mismatched, num_quads, strict=True)
+    assert excinfo.value.channel == "IO"
+    assert excinfo.value.block_index == 0
+
+
+def test_read_scode_rejects_missing_table_entry():
+    decoder = FDBAQDecoder(b"\x00\x00", 0)
+    with pytest.raises(InvalidHuffmanCodeError):
+        decoder._read_scode(_TREES[0], 2, [None] * 4)
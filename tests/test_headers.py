@@ -1,4 +1,6 @@
-from sentinel1decoder._headers import decode_primary_header
+from sentinel1decoder._headers import decode_primary_header, decode_secondary_header
+
+import sentinel1decoder.constants as cnst
 
 import pytest
 
@@ -10,4 +12,19 @@ def test_decode_primary_header():
     with pytest.raises(Exception):
         decode_primary_header(0xFFFFFFFFFFFFFF)
 
-    # TODO: More tests here - get some mock data
\ No newline at end of file
+    # TODO: More tests here - get some mock data
+
+
+def test_decode_secondary_header_reserved_bits():
+    header_bytes = bytearray(62)
+    header_bytes[6:10] = cnst.SYNC_MARKER.to_bytes(4, "big")
+    header_bytes[15] = 0x80 | 0x07  # Unused top bit, Rx channel ID 7
+    header_bytes[33] = 0x5a
+
+    assert cnst.RESERVED_BITS_FIELD_NAME.format(15) not in decode_secondary_header(bytes(header_bytes))
+
+    fields = decode_secondary_header(bytes(header_bytes), reserved_bits=True)
+    assert fields[cnst.RX_CHAN_ID_FIELD_NAME] == 7
+    assert fields[cnst.RESERVED_BITS_FIELD_NAME.format(15)] == 0x80
+    assert fields[cnst.RESERVED_BITS_FIELD_NAME.format(33)] == 0x5a
+    assert fields[cnst.RESERVED_BITS_FIELD_NAME.format(43)] == 0
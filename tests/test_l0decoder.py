@@ -0,0 +1,363 @@
+import threading
+
+import numpy as np
+import pandas as pd
+import pytest
+
+from sentinel1decoder.l0decoder import Level0Decoder, FdbaqPacketIterator, StreamingHeaderParser, decode_file_to_npy
+
+import sentinel1decoder.constants as cnst
+from sentinel1decoder.exceptions import InvalidBaqModeError, InvalidSyncMarkerError
+
+from .helpers import echo_packet, fdbaq_packet, packet_without_secondary_header
+
+
+def test_streaming_header_parser():
+    first = packet_without_secondary_header(1, bytes(range(6)))
+    second = packet_without_secondary_header(2, bytes(range(10, 20)))
+    stream = first + second
+
+    parser = StreamingHeaderParser()
+    assert parser.push(stream[:4]) == []
+
+    packets = parser.push(stream[4:len(first) + 3])
+    assert len(packets) == 1
+    header, bounds, user_data = packets[0]
+    assert header[cnst.PACKET_SEQUENCE_COUNT_FIELD_NAME] == 1
+    assert bounds == (6, 6)
+    assert user_data == bytes(range(6))
+    assert parser.buffered_bytes == 3
+
+    packets = parser.push(stream[len(first) + 3:])
+    assert len(packets) == 1
+    header, bounds, user_data = packets[0]
+    assert header[cnst.PACKET_SEQUENCE_COUNT_FIELD_NAME] == 2
+    assert stream[bounds[0]:bounds[0] + bounds[1]] == user_data == bytes(range(10, 20))
+    assert parser.buffered_bytes == 0
+
+
+def test_streaming_header_parser_skips_short_secondary_header():
+    num_quads = 16
+    user_data, _, _ = fdbaq_packet(num_quads)
+    # Secondary header flag set, but only 22 bytes of packet data
+    malformed = bytes([0x08, 0x00, 0xc0, 0x02, 0x00, 21]) + bytes(22)
+    good = echo_packet(1, num_quads, user_data)
+
+    parser = StreamingHeaderParser()
+    packets = parser.push(malformed + good[:10])
+    assert packets == []
+    assert parser.buffered_bytes == 10
+
+    packets = parser.push(good[10:])
+    assert len(packets) == 1
+    header, bounds, packet_user_data = packets[0]
+    assert header[cnst.SPACE_PACKET_COUNT_FIELD_NAME] == 1
+    assert bounds == (len(malformed) + 68, len(good) - 68)
+    assert packet_user_data == good[68:]
+    assert parser.buffered_bytes == 0
+
+
+def test_decode_metadata_drops_truncated_packet(tmp_path):
+    first = packet_without_secondary_header(1, bytes(6))
+    second = packet_without_secondary_header(2, bytes(10))
+    filename = tmp_path / "truncated.dat"
+    filename.write_bytes(first + second[:-4])
+
+    decoder = Level0Decoder(str(filename))
+    metadata = decoder.decode_metadata()
+    assert len(metadata) == 1
+    assert metadata.attrs[cnst.TRUNCATED_PACKETS_ATTR_NAME] == 1
+    assert list(decoder.packet_offsets()) == [0]
+
+    filename.write_bytes(first + second)
+    assert decoder.decode_metadata().attrs[cnst.TRUNCATED_PACKETS_ATTR_NAME] == 0
+
+
+def test_decode_metadata_drops_final_packet_with_short_secondary_header(tmp_path):
+    first = packet_without_secondary_header(1, bytes(6))
+    # Secondary header flag set, but only 22 bytes of packet data
+    short = bytes([0x08, 0x00, 0xc0, 0x02, 0x00, 21]) + bytes(22)
+    filename = tmp_path / "short_secondary_header.dat"
+    filename.write_bytes(first + short)
+
+    metadata = Level0Decoder(str(filename)).decode_metadata()
+    assert len(metadata) == 1
+    assert metadata.attrs[cnst.TRUNCATED_PACKETS_ATTR_NAME] == 1
+
+
+def test_parallel_metadata_matches_sequential(tmp_path):
+    num_quads = 16
+    user_data, _, _ = fdbaq_packet(num_quads)
+
+    filename = tmp_path / "mixed.dat"
+    filename.write_bytes(
+        echo_packet(1, num_quads, user_data)
+        + packet_without_secondary_header(2, bytes(6))
+        + echo_packet(3, num_quads, user_data)
+    )
+    decoder = Level0Decoder(str(filename))
+
+    sequential = decoder.decode_metadata()
+    parallel = decoder.decode_metadata(num_workers=2)
+    assert list(parallel[cnst.SECONDARY_HEADER_FIELD_NAME]) == [1, 0, 1]
+    pd.testing.assert_frame_equal(parallel, sequential)
+    assert parallel.attrs == sequential.attrs
+
+
+def test_user_data_bounds(tmp_path):
+    num_quads = 16
+    user_data, _, _ = fdbaq_packet(num_quads)
+    first = echo_packet(1, num_quads, user_data)
+    second = packet_without_secondary_header(2, bytes(range(6)))
+    third = echo_packet(3, num_quads, user_data)
+
+    filename = tmp_path / "mixed.dat"
+    filename.write_bytes(first + second + third)
+
+    bounds = Level0Decoder(str(filename)).user_data_bounds()
+    assert bounds.tolist() == [
+        [68, len(first) - 68],
+        [len(first) + 6, 6],
+        [len(first) + len(second) + 68, len(third) - 68],
+    ]
+
+
+def test_decode_file_skips_packets_without_user_data(tmp_path):
+    num_quads = 16
+    user_data, _, _ = fdbaq_packet(num_quads)
+
+    filename = tmp_path / "header_only.dat"
+    filename.write_bytes(echo_packet(1, num_quads, user_data) + echo_packet(2, num_quads, b"") + echo_packet(3, num_quads, user_data))
+
+    output_data, headers = Level0Decoder(str(filename)).decode_file()
+    assert list(headers[cnst.SPACE_PACKET_COUNT_FIELD_NAME]) == [1, 3]
+    assert output_data.shape == (2, 2 * num_quads)
+    np.testing.assert_array_equal(output_data[0], output_data[1])
+    assert np.abs(output_data).sum() > 0
+
+
+def test_decode_file_to_npy(tmp_path):
+    packets = [
+        echo_packet(count, num_quads, fdbaq_packet(num_quads, seed=count)[0])
+        for count, num_quads in enumerate((16, 20, 16, 16))
+    ]
+
+    input_path = tmp_path / "scene.dat"
+    input_path.write_bytes(b"".join(packets))
+    output_path = tmp_path / "scene.npy"
+
+    assert decode_file_to_npy(str(input_path), str(output_path), 16, dtype="complex64") == 3
+    output_data = np.load(output_path, mmap_mode="r")
+    assert output_data.shape == (3, 32)
+    assert output_data.dtype == np.complex64
+    np.testing.assert_array_equal(output_data, np.stack(list(FdbaqPacketIterator(str(input_path), 16))).astype(np.complex64))
+
+
+def test_swap_flag_only_applied_when_requested(tmp_path):
+    num_quads = 16
+    user_data, _, _ = fdbaq_packet(num_quads)
+
+    filename = tmp_path / "swapped.dat"
+    filename.write_bytes(echo_packet(1, num_quads, user_data) + echo_packet(2, num_quads, user_data, swap=1))
+    decoder = Level0Decoder(str(filename))
+
+    output_data, headers = decoder.decode_file()
+    assert list(headers[cnst.SWAP_FLAG_FIELD_NAME]) == [0, 1]
+    np.testing.assert_array_equal(output_data[0], output_data[1])
+
+    output_data, _ = decoder.decode_file(respect_swap=True)
+    np.testing.assert_array_equal(output_data[1].real, output_data[0].imag)
+    np.testing.assert_array_equal(output_data[1].imag, output_data[0].real)
+
+
+def test_fdbaq_packet_iterator_context_manager():
+    num_quads = 16
+    user_data, _, _ = fdbaq_packet(num_quads)
+    stream = echo_packet(1, num_quads, user_data) + echo_packet(2, num_quads, b"") + echo_packet(3, num_quads, user_data)
+
+    with FdbaqPacketIterator(stream) as packets:
+        first = next(packets)
+    assert packets._file.closed
+
+    rows = list(FdbaqPacketIterator(stream))
+    assert len(rows) == 2
+    np.testing.assert_array_equal(rows[0], first)
+
+
+def test_decode_file_to_npy_truncated(tmp_path):
+    num_quads = 16
+    packets = [echo_packet(count, num_quads, fdbaq_packet(num_quads, seed=count)[0]) for count in range(3)]
+
+    input_path = tmp_path / "truncated.dat"
+    input_path.write_bytes(b"".join(packets)[:-10])
+    output_path = tmp_path / "truncated.npy"
+
+    assert decode_file_to_npy(str(input_path), str(output_path), num_quads) == 2
+    output_data = np.load(output_path)
+    rows = list(FdbaqPacketIterator(str(input_path), num_quads))
+    assert len(rows) == 2
+    np.testing.assert_array_equal(output_data, np.stack(rows))
+
+
+def test_fdbaq_packet_iterator_rejects_reserved_baq_mode():
+    num_quads = 16
+    user_data, _, _ = fdbaq_packet(num_quads)
+    stream = echo_packet(1, num_quads, user_data) + echo_packet(2, num_quads, user_data, baq_mode=15)
+
+    packets = FdbaqPacketIterator(stream)
+    next(packets)
+    with pytest.raises(InvalidBaqModeError) as excinfo:
+        next(packets)
+    assert excinfo.value.baq_mode == 15
+    packets.close()
+
+
+def test_parallel_decode_matches_sequential(tmp_path):
+    num_quads = 16
+    packets = [echo_packet(count, num_quads, fdbaq_packet(num_quads, seed=count)[0]) for count in range(5)]
+
+    filename = tmp_path / "scene.dat"
+    filename.write_bytes(b"".join(packets))
+    decoder = Level0Decoder(str(filename))
+    metadata = decoder.decode_metadata()
+
+    sequential = decoder.decode_packets(metadata)
+    # Decode even this small batch in the worker processes
+    parallel = decoder.decode_packets(metadata, num_workers=2, progress_interval=2, min_parallel_quads=0)
+    assert parallel.shape == (5, 2 * num_quads)
+    np.testing.assert_array_equal(parallel, sequential)
+
+
+@pytest.mark.parametrize("num_workers", [None, 2])
+def test_strict_metadata_rejects_invalid_sync_marker(tmp_path, num_workers):
+    num_quads = 16
+    user_data, _, _ = fdbaq_packet(num_quads)
+    first = echo_packet(1, num_quads, user_data)
+
+    filename = tmp_path / "corrupt.dat"
+    filename.write_bytes(first + echo_packet(2, num_quads, user_data, sync=0))
+
+    with pytest.raises(InvalidSyncMarkerError) as excinfo:
+        Level0Decoder(str(filename)).decode_metadata(strict=True, num_workers=num_workers)
+    assert (excinfo.value.packet_index, excinfo.value.offset) == (1, len(first))
+
+
+def test_decode_packets_by_signal_type(tmp_path):
+    num_quads = 16
+    user_data, _, _ = fdbaq_packet(num_quads)
+
+    filename = tmp_path / "mixed.dat"
+    filename.write_bytes(
+        echo_packet(1, num_quads, user_data)
+        + echo_packet(2, num_quads, user_data, signal_type=cnst.NOISE_SIGNAL_TYPE)
+        + echo_packet(3, num_quads, user_data)
+    )
+    decoder = Level0Decoder(str(filename))
+    metadata = decoder.decode_metadata()
+
+    output_data, index = decoder.decode_packets_by_signal_type(metadata, [cnst.ECHO_SIGNAL_TYPE], dtype="complex64")
+    assert list(index) == [0, 2]
+    assert output_data.shape == (2, 2 * num_quads)
+    assert output_data.dtype == np.complex64
+    np.testing.assert_array_equal(output_data, decoder.decode_packets(metadata.loc[index], dtype="complex64"))
+
+
+def test_decode_packets_skips_packets_without_user_data(tmp_path):
+    num_quads = 16
+    user_data, _, _ = fdbaq_packet(num_quads)
+
+    filename = tmp_path / "header_only.dat"
+    filename.write_bytes(echo_packet(1, num_quads, user_data) + echo_packet(2, num_quads, b"") + echo_packet(3, num_quads, user_data))
+    decoder = Level0Decoder(str(filename))
+
+    metadata = decoder.decode_metadata()
+    assert list(metadata[cnst.HAS_USER_DATA_FIELD_NAME]) == [True, False, True]
+    assert list(decoder.decode_metadata(num_workers=2)[cnst.HAS_USER_DATA_FIELD_NAME]) == [True, False, True]
+
+    output_data = decoder.decode_packets(metadata)
+    assert output_data.shape == (2, 2 * num_quads)
+    np.testing.assert_array_equal(output_data[0], output_data[1])
+    assert np.abs(output_data).sum() > 0
+
+
+def test_decode_metadata_malformed_packet(tmp_path):
+    num_quads = 16
+    user_data, _, _ = fdbaq_packet(num_quads)
+    first = echo_packet(1, num_quads, user_data)
+    # Secondary header flag set, but only 22 bytes of packet data
+    malformed = bytes([0x08, 0x00, 0xc0, 0x02, 0x00, 21]) + bytes(22)
+
+    filename = tmp_path / "malformed.dat"
+    filename.write_bytes(first + malformed + echo_packet(3, num_quads, user_data))
+    decoder = Level0Decoder(str(filename))
+
+    with pytest.raises(ValueError, match=f"packet 1 at byte offset {len(first)}"):
+        decoder.decode_metadata()
+
+    metadata = decoder.decode_metadata(partial=True)
+    assert list(metadata[cnst.SPACE_PACKET_COUNT_FIELD_NAME]) == [1]
+
+
+def _scene(tmp_path, num_packets, num_quads=16):
+    packets = [echo_packet(count, num_quads, fdbaq_packet(num_quads, seed=count)[0]) for count in range(num_packets)]
+    filename = tmp_path / "scene.dat"
+    filename.write_bytes(b"".join(packets))
+    decoder = Level0Decoder(str(filename))
+    return decoder, decoder.decode_metadata()
+
+
+@pytest.mark.parametrize("num_workers", [None, 2])
+def test_decode_packets_progress_callback(tmp_path, num_workers):
+    decoder, metadata = _scene(tmp_path, 5)
+
+    progress = []
+    decoder.decode_packets(metadata, progress_callback=progress.append, progress_interval=2, num_workers=num_workers)
+    assert progress == [2, 4, 5]
+
+
+@pytest.mark.parametrize("num_workers", [None, 2])
+def test_decode_packets_cancel_event(tmp_path, num_workers):
+    decoder, metadata = _scene(tmp_path, 5)
+    full_output = decoder.decode_packets(metadata)
+
+    # Cancel from the progress callback once the first two packets are decoded
+    cancel_event = threading.Event()
+    output_data = decoder.decode_packets(
+        metadata,
+        progress_callback=lambda count: cancel_event.set(),
+        progress_interval=2,
+        cancel_event=cancel_event,
+        num_workers=num_workers
+    )
+    assert output_data.shape == (2, 32)
+    np.testing.assert_array_equal(output_data, full_output[:2])
+
+
+def test_decode_file_from_mapped_user_data(tmp_path):
+    num_quads = 16
+    packets = [echo_packet(count, num_quads, fdbaq_packet(num_quads, seed=count)[0]) for count in range(3)]
+
+    filename = tmp_path / "scene.dat"
+    filename.write_bytes(packets[0] + packet_without_secondary_header(1, bytes(6)) + b"".join(packets[1:]) + packets[0][:30])
+
+    output_data, headers = Level0Decoder(str(filename)).decode_file(check_lengths=True)
+    assert list(headers[cnst.SPACE_PACKET_COUNT_FIELD_NAME]) == [0, 1, 2]
+    assert not headers[cnst.USER_DATA_RESIDUAL_FIELD_NAME].isna().any()
+    np.testing.assert_array_equal(output_data, np.stack(list(FdbaqPacketIterator(str(filename)))))
+
+
+def test_decode_file_to_npy_corrupt_packet(tmp_path):
+    num_quads = 16
+    user_data, _, _ = fdbaq_packet(num_quads)
+
+    # The second packet's user data ends long before all its samples
+    input_path = tmp_path / "corrupt.dat"
+    input_path.write_bytes(echo_packet(1, num_quads, user_data) + echo_packet(2, num_quads, user_data[:4]) + echo_packet(3, num_quads, user_data))
+    output_path = tmp_path / "corrupt.npy"
+
+    assert decode_file_to_npy(str(input_path), str(output_path), num_quads) == 3
+    output_data = np.load(output_path)
+    assert output_data.shape == (3, 2 * num_quads)
+    assert not output_data[1].any()
+    np.testing.assert_array_equal(output_data[0], output_data[2])
+    assert output_data[0].any()
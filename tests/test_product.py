@@ -0,0 +1,27 @@
+import pytest
+
+from sentinel1decoder.product import Product
+
+import sentinel1decoder.constants as cnst
+
+
+def test_product_headers_and_close(tmp_path):
+    # Two packets without secondary headers, followed by a truncated packet
+    packets = b"".join(
+        bytes([0x00, 0x00, 0x00, count, 0x00, 0x05]) + bytes(6)
+        for count in (1, 2, 3)
+    )
+    filename = tmp_path / "product.dat"
+    filename.write_bytes(packets[:-2])
+
+    with Product(str(filename)) as product:
+        assert len(product) == 2
+        assert list(product.headers()[cnst.PACKET_SEQUENCE_COUNT_FIELD_NAME]) == [1, 2]
+
+        # Packets without a secondary header have no samples to decode
+        data, headers = product.decode_range(0, 2)
+        assert data.shape[0] == 0
+        assert len(headers) == 0
+
+    with pytest.raises(ValueError):
+        product.decode_range(0, 2)
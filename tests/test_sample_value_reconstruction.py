@@ -0,0 +1,62 @@
+import numpy as np
+import pytest
+
+import sentinel1decoder._sample_value_reconstruction as rec
+from sentinel1decoder._sample_code import SampleCode
+from sentinel1decoder._sample_value_reconstruction import reconstruct_channel
+from sentinel1decoder._user_data_decoder import decode_single_fdbaq_packet, decode_single_fdbaq_packet_codes
+
+from .helpers import fdbaq_packet
+
+
+def _thidx_cases():
+    # THIDXs either side of each BRC's switch from the simple to the normal
+    # reconstruction method, along with the smallest and largest THIDX
+    for brc, (max_thidx, _, _) in rec._SIMPLE_RECONSTRUCTION_PARAMS.items():
+        for thidx in sorted({0, max_thidx - 1, max_thidx, max_thidx + 1, 255}):
+            yield brc, thidx
+
+
+@pytest.mark.parametrize("brc, thidx", list(_thidx_cases()))
+def test_reconstruct_block_matches_per_sample(brc, thidx):
+    max_mcode = rec._SIMPLE_RECONSTRUCTION_PARAMS[brc][1]
+    mcodes = np.repeat(np.arange(max_mcode + 1), 2)
+    signs = np.tile([False, True], max_mcode + 1)
+
+    block_vals = rec._reconstruct_block(mcodes, signs, brc, thidx)
+    sample_vals = [
+        rec._reconstruct_sample(SampleCode(int(sign), int(mcode)), brc, thidx, 0, n, False)
+        for n, (mcode, sign) in enumerate(zip(mcodes, signs))
+    ]
+    assert block_vals is not None
+    np.testing.assert_array_equal(block_vals, sample_vals)
+
+
+def test_reconstruct_channel_matches_packet_decode():
+    num_quads = 300
+    data, _, _ = fdbaq_packet(num_quads, brcs=[0, 3, 4], thidxs=[2, 7, 200])
+    mcodes, signs, brcs, thidxs = decode_single_fdbaq_packet_codes(data, num_quads)
+    decoded = decode_single_fdbaq_packet(data, num_quads)
+
+    # IE, IO, QE and QO are the even and odd samples of the I and Q parts
+    expected = (decoded.real[0::2], decoded.real[1::2], decoded.imag[0::2], decoded.imag[1::2])
+    for channel in range(4):
+        vals = reconstruct_channel(signs[channel], mcodes[channel], brcs, thidxs)
+        assert vals.dtype == np.float32
+        np.testing.assert_array_equal(vals, expected[channel].astype(np.float32))
+
+    with pytest.raises(ValueError):
+        reconstruct_channel(signs[0][:-1], mcodes[0], brcs, thidxs)
+    with pytest.raises(ValueError):
+        reconstruct_channel(signs[0], mcodes[0], brcs[:2], thidxs[:2])
+
+
+def test_reconstruct_channel_saturation_mask():
+    # The largest magnitude code of each block's BRC is flagged, under both
+    # the simple (THIDX 0) and normal (THIDX 100) reconstruction methods
+    mcodes = [0, 3, 1, 3, 15, 14, 0, 15]
+    signs = [False, True] * 4
+
+    vals, saturated = reconstruct_channel(signs, mcodes, [0, 4], [0, 100], block_size=4, return_saturation=True)
+    np.testing.assert_array_equal(saturated, [False, True, False, True, True, False, False, True])
+    np.testing.assert_array_equal(vals, reconstruct_channel(signs, mcodes, [0, 4], [0, 100], block_size=4))
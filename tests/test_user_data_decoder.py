@@ -0,0 +1,332 @@
+import asyncio
+import math
+
+import numpy as np
+import pytest
+
+import sentinel1decoder.constants as cnst
+import sentinel1decoder._sample_value_reconstruction as rec
+from sentinel1decoder._fdbaq_decoder import FDBAQDecoder
+from sentinel1decoder._sample_code import SampleCode
+from sentinel1decoder._user_data_decoder import (
+    BaqMode,
+    user_data_decoder,
+    decode_single_bypass_packet,
+    decode_single_bypass_packet_channels,
+    decode_single_fdbaq_packet,
+    decode_single_fdbaq_packet_window,
+    decode_single_fdbaq_packet_quant,
+    decode_single_fdbaq_packet_brc_hist,
+    decode_single_fdbaq_packet_codes,
+    decode_single_fdbaq_packet_downsampled,
+    decode_fdbaq_packets_from_bounds,
+    decode_fdbaq_packets_from_ndarray,
+    decode_fdbaq_packets_async
+)
+from sentinel1decoder.exceptions import InvalidBaqModeError, InvalidBoundsError, UnrepresentableSampleError
+from sentinel1decoder.l0decoder import Level0Decoder, _decode_packet_rows
+
+from .helpers import echo_packet, fdbaq_packet
+
+
+def _encode_bypass_channel(values, num_quads):
+    # Pack sign-magnitude 10-bit words, padded to a whole number of 16-bit words
+    bits = "".join(f"{(1 << 9 if v < 0 else 0) | abs(v):010b}" for v in values)
+    num_bytes = 2 * math.ceil((10/16) * num_quads)
+    bits = bits.ljust(8 * num_bytes, "0")
+    return int(bits, 2).to_bytes(num_bytes, "big")
+
+
+def _encode_bypass_packet(ie, io, qe, qo):
+    num_quads = len(ie)
+    return b"".join(_encode_bypass_channel(ch, num_quads) for ch in (ie, io, qe, qo))
+
+
+def test_decode_bypass_packet_swap():
+    ie, io, qe, qo = [1, -2, 3, -4], [5, 6, -7, 8], [-9, 10, 11, -12], [13, -14, 15, 16]
+    data = _encode_bypass_packet(ie, io, qe, qo)
+
+    samples = decode_single_bypass_packet(data, 4)
+    np.testing.assert_array_equal(samples.real, [1, 5, -2, 6, 3, -7, -4, 8])
+    np.testing.assert_array_equal(samples.imag, [-9, 13, 10, -14, 11, 15, -12, 16])
+
+    swapped = decode_single_bypass_packet(data, 4, swap=True)
+    np.testing.assert_array_equal(swapped.real, samples.imag)
+    np.testing.assert_array_equal(swapped.imag, samples.real)
+
+
+def test_decode_bypass_packet_channels():
+    ie, io, qe, qo = [1, -2, 3], [4, 5, -6], [-7, 8, 9], [10, -11, 12]
+    data = _encode_bypass_packet(ie, io, qe, qo)
+
+    channels = decode_single_bypass_packet_channels(data, 3)
+    for decoded, expected in zip(channels, (ie, io, qe, qo)):
+        assert decoded.dtype == np.float32
+        np.testing.assert_array_equal(decoded, expected)
+
+
+def test_decode_bypass_packet_exact_fit():
+    ie, io, qe, qo = [1, -2, 3], [4, 5, -6], [-7, 8, 9], [10, -11, 12]
+    data = _encode_bypass_packet(ie, io, qe, qo)
+
+    # Drop the padding after the final 30-bit channel, leaving its last byte
+    # as the last byte of the buffer
+    data = data[:3 * 4 + 4]
+
+    channels = decode_single_bypass_packet_channels(data, 3)
+    np.testing.assert_array_equal(channels[3], qo)
+
+
+def test_decode_bypass_packet_byte_order():
+    data = _encode_bypass_packet([1, -2], [3, 4], [-5, 6], [7, -8])
+
+    native = decode_single_bypass_packet(data, 2, dtype="complex64")
+    big_endian = decode_single_bypass_packet(data, 2, dtype=">c8")
+    assert big_endian.dtype.byteorder == (">" if np.little_endian else "=")
+    np.testing.assert_array_equal(big_endian, native)
+
+
+def test_decode_zero_quad_packets():
+    assert decode_single_bypass_packet(b"", 0).shape == (0,)
+    assert all(ch.shape == (0,) for ch in decode_single_bypass_packet_channels(b"", 0))
+    assert decode_single_fdbaq_packet(b"", 0).shape == (0,)
+
+    batch = decode_fdbaq_packets_from_bounds(b"", [(0, 0), (0, 0)], 0, dtype="complex64")
+    assert batch.shape == (2, 0)
+    assert batch.dtype == np.complex64
+
+
+def test_decode_zero_quad_packet_rows():
+    header_rows = [
+        {
+            cnst.BAQ_MODE_FIELD_NAME: baq_mode,
+            cnst.NUM_QUADS_FIELD_NAME: 0,
+            cnst.SWAP_FLAG_FIELD_NAME: 0,
+            cnst.SPACE_PACKET_COUNT_FIELD_NAME: i
+        }
+        for i, baq_mode in enumerate((0, 12))
+    ]
+
+    output_data, headers = _decode_packet_rows(header_rows, [b"", b""], pad=False, dtype="complex64", check_lengths=True)
+    assert output_data.shape == (2, 0)
+    np.testing.assert_array_equal(headers[cnst.USER_DATA_RESIDUAL_FIELD_NAME], [0, 0])
+
+
+def test_decode_fdbaq_packet_window():
+    num_quads = 300
+    data, _, _ = fdbaq_packet(num_quads, brcs=[1, 2, 4], thidxs=[3, 20, 100])
+
+    samples = decode_single_fdbaq_packet(data, num_quads)
+    window = decode_single_fdbaq_packet_window(data, num_quads, 1, 2)
+    np.testing.assert_array_equal(window, samples[2 * 128:])
+
+    window = decode_single_fdbaq_packet_window(data, num_quads, 0, 1, dtype="complex64")
+    assert window.dtype == np.complex64
+    np.testing.assert_array_equal(window, samples[:2 * 128])
+
+
+def test_decode_bypass_packet_padding():
+    data = bytearray(_encode_bypass_packet([1, -2, 3], [4, 5, -6], [-7, 8, 9], [10, -11, 12]))
+
+    # Each 30-bit channel is padded to 32 bits
+    _, padding_bits, nonzero = decode_single_bypass_packet(data, 3, return_padding=True)
+    np.testing.assert_array_equal(padding_bits, [2, 2, 2, 2])
+    assert not nonzero.any()
+
+    # Set the last padding bit of the IO channel
+    data[7] |= 1
+    _, _, nonzero = decode_single_bypass_packet(data, 3, return_padding=True)
+    np.testing.assert_array_equal(nonzero, [False, True, False, False])
+
+
+def test_decode_fdbaq_packet_quant():
+    num_quads = 130
+    data, mcodes, signs = fdbaq_packet(num_quads, brcs=[3, 0], thidxs=[7, 250])
+
+    sample_brcs, sample_thidxs, sample_mcodes, sample_signs = decode_single_fdbaq_packet_quant(data, num_quads)
+    assert sample_brcs.shape == sample_thidxs.shape == sample_mcodes.shape == sample_signs.shape == (4, num_quads)
+    np.testing.assert_array_equal(sample_brcs[:, :128], 3)
+    np.testing.assert_array_equal(sample_brcs[:, 128:], 0)
+    np.testing.assert_array_equal(sample_thidxs[:, 128:], 250)
+    np.testing.assert_array_equal(sample_mcodes, mcodes)
+    np.testing.assert_array_equal(sample_signs, signs)
+
+
+def test_decode_fdbaq_packets_fortran_order():
+    num_quads = 130
+    data, _, _ = fdbaq_packet(num_quads, brcs=[1, 3], thidxs=[4, 9])
+    bounds = [(0, len(data))] * 3
+
+    c_order = decode_fdbaq_packets_from_bounds(data, bounds, num_quads)
+    f_order = decode_fdbaq_packets_from_bounds(data, bounds, num_quads, order="F")
+    assert f_order.flags.f_contiguous
+    np.testing.assert_array_equal(f_order, c_order)
+
+
+def test_decode_fdbaq_packets_failure_mask():
+    num_quads = 20
+    data, _, _ = fdbaq_packet(num_quads)
+
+    # The second packet's bounds cut its data short
+    bounds = [(0, len(data)), (0, 4), (0, len(data))]
+    output_data, decoded = decode_fdbaq_packets_from_bounds(data, bounds, num_quads, return_mask=True)
+    np.testing.assert_array_equal(decoded, [True, False, True])
+    assert np.isnan(output_data[1]).all()
+    np.testing.assert_array_equal(output_data[0], output_data[2])
+    assert not np.isnan(output_data[0]).any()
+
+
+@pytest.mark.parametrize("start, length", [(-1, 8), (0, -1), (8, 100)])
+def test_decode_fdbaq_packets_invalid_bounds(start, length):
+    num_quads = 20
+    data, _, _ = fdbaq_packet(num_quads)
+    bounds = [(0, len(data)), (start, length)]
+
+    with pytest.raises(InvalidBoundsError) as excinfo:
+        decode_fdbaq_packets_from_bounds(data, bounds, num_quads)
+    assert excinfo.value.packet_index == 1
+
+    _, decoded = decode_fdbaq_packets_from_bounds(data, bounds, num_quads, return_mask=True)
+    np.testing.assert_array_equal(decoded, [True, False])
+
+
+def test_decode_fdbaq_packets_from_ndarray():
+    num_quads = 40
+    packets = [fdbaq_packet(num_quads, thidxs=[seed], seed=seed)[0] for seed in range(3)]
+    row_length = max(len(data) for data in packets)
+    array = np.zeros((3, row_length), dtype=np.uint8)
+    for row, data in zip(array, packets):
+        row[:len(data)] = np.frombuffer(data, dtype=np.uint8)
+
+    output_data = decode_fdbaq_packets_from_ndarray(array, num_quads)
+    for row, data in zip(output_data, packets):
+        np.testing.assert_array_equal(row, decode_single_fdbaq_packet(data, num_quads))
+
+
+def test_decode_fdbaq_packet_downsampled():
+    num_quads = 25
+    data, _, _ = fdbaq_packet(num_quads, brcs=[2], thidxs=[11])
+    samples = decode_single_fdbaq_packet(data, num_quads, dtype="complex64")
+
+    decimated = decode_single_fdbaq_packet_downsampled(data, num_quads, 4)
+    np.testing.assert_array_equal(decimated, samples[::4])
+
+    # 50 samples give 12 full groups of 4 and a final group of 2
+    averaged = decode_single_fdbaq_packet_downsampled(data, num_quads, 4, method="mean")
+    assert averaged.dtype == np.complex64
+    assert len(averaged) == 13
+    np.testing.assert_allclose(averaged[0], samples[:4].mean(), rtol=1e-6)
+    np.testing.assert_allclose(averaged[-1], samples[48:].mean(), rtol=1e-6)
+
+
+def test_decode_fdbaq_packets_async():
+    num_quads = 30
+    data, _, _ = fdbaq_packet(num_quads, brcs=[1], thidxs=[3])
+
+    output_data = asyncio.run(decode_fdbaq_packets_async([data, data], num_quads, dtype="complex64"))
+    assert output_data.shape == (2, 2 * num_quads)
+    np.testing.assert_array_equal(output_data[1], decode_single_fdbaq_packet(data, num_quads))
+
+
+def test_decode_fdbaq_packet_blocked_layout():
+    num_quads = 10
+    data, _, _ = fdbaq_packet(num_quads, thidxs=[1])
+
+    interleaved = decode_single_fdbaq_packet(data, num_quads)
+    blocked = decode_single_fdbaq_packet(data, num_quads, interleave=False)
+    np.testing.assert_array_equal(blocked.reshape(2, num_quads), [interleaved[0::2], interleaved[1::2]])
+
+    batch = decode_fdbaq_packets_from_bounds(data, [(0, len(data))], num_quads, interleave=False)
+    np.testing.assert_array_equal(batch[0], blocked)
+
+
+def test_decode_fdbaq_packets_profile():
+    num_quads = 12
+    data, _, _ = fdbaq_packet(num_quads)
+    bounds = [(0, len(data)), (0, 2)]
+
+    output_data, decoded, timings = decode_fdbaq_packets_from_bounds(data, bounds, num_quads, return_mask=True, profile=True)
+    assert output_data.shape == (2, 2 * num_quads)
+    np.testing.assert_array_equal(decoded, [True, False])
+    assert timings.dtype == np.int64
+    assert (timings > 0).all()
+
+
+def test_baq_mode_from_code():
+    assert BaqMode.from_code(0).is_bypass
+    assert BaqMode.from_code(4).is_baq
+    assert BaqMode.from_code(13).is_fdbaq
+    assert not BaqMode.from_code(12).is_baq
+
+    for reserved in (1, 6, 11, 15, 31):
+        with pytest.raises(InvalidBaqModeError):
+            BaqMode.from_code(reserved)
+        with pytest.raises(InvalidBaqModeError):
+            user_data_decoder(b"", reserved, 0)
+
+
+def test_decode_fdbaq_packet_brc_hist():
+    data, _, _ = fdbaq_packet(600, brcs=[0, 3, 4, 3, 1], thidxs=[2, 10, 200, 4, 7])
+
+    hist = decode_single_fdbaq_packet_brc_hist(data, 600)
+    assert hist.dtype == np.uint32
+    np.testing.assert_array_equal(hist, [1, 1, 0, 2, 1])
+    np.testing.assert_array_equal(decode_single_fdbaq_packet_brc_hist(b"", 0), [0, 0, 0, 0, 0])
+
+
+def test_decode_fdbaq_codes_at_end_of_data(monkeypatch):
+    # Samples too close to the end of the data for a full lookup table index
+    # are read by walking the Huffman tree instead
+    tree_reads = []
+    read_scode_from_tree = FDBAQDecoder._read_scode_from_tree
+    monkeypatch.setattr(
+        FDBAQDecoder,
+        "_read_scode_from_tree",
+        lambda self, huffman_tree: tree_reads.append(self._byte_counter) or read_scode_from_tree(self, huffman_tree)
+    )
+
+    for brc in range(5):
+        for num_quads in range(1, 9):
+            data, expected_mcodes, expected_signs = fdbaq_packet(num_quads, brcs=[brc])
+            mcodes, signs, _, _ = decode_single_fdbaq_packet_codes(data, num_quads)
+            np.testing.assert_array_equal(mcodes, expected_mcodes)
+            np.testing.assert_array_equal(signs, expected_signs)
+
+    assert tree_reads
+
+
+@pytest.mark.parametrize("thidx", [0, 10])
+def test_unrepresentable_sample(thidx):
+    # BRC 0 magnitude codes only go up to 3, under both the simple (THIDX 0)
+    # and normal (THIDX 10) reconstruction methods
+    scodes = [SampleCode(0, 1)] * 8
+    scodes[6] = SampleCode(1, 7)
+
+    with pytest.raises(UnrepresentableSampleError) as excinfo:
+        rec.reconstruct_channel_vals(scodes, [0, 0], [thidx, thidx], 8, block_size=4)
+    error = excinfo.value
+    assert (error.block_index, error.sample_index, error.mcode, error.brc, error.thidx) == (1, 6, 7, 0, thidx)
+
+    vals = rec.reconstruct_channel_vals(scodes, [0, 0], [thidx, thidx], 8, block_size=4, lenient=True)
+    assert np.isnan(vals[6])
+    assert not np.isnan(np.delete(vals, 6)).any()
+
+
+def test_decode_metadata_resync(tmp_path):
+    num_quads = 16
+    user_data, _, _ = fdbaq_packet(num_quads)
+    first = echo_packet(1, num_quads, user_data)
+    corrupt = echo_packet(2, num_quads, user_data, sync=0)
+    third = echo_packet(3, num_quads, user_data)
+
+    filename = tmp_path / "corrupt.dat"
+    filename.write_bytes(first + corrupt + third)
+    decoder = Level0Decoder(str(filename))
+
+    metadata = decoder.decode_metadata(resync=True)
+    assert list(metadata[cnst.SPACE_PACKET_COUNT_FIELD_NAME]) == [1, 3]
+    assert metadata.attrs[cnst.SKIPPED_REGIONS_ATTR_NAME] == [(len(first), len(first) + len(corrupt))]
+
+    # Without resync, the packet is kept and only flagged
+    metadata = decoder.decode_metadata()
+    assert list(metadata[cnst.SYNC_VALID_FIELD_NAME]) == [True, False, True]
@@ -1,7 +1,12 @@
-from sentinel1decoder.utilities import range_dec_to_sample_rate
+from sentinel1decoder.utilities import range_dec_to_sample_rate, polarisation_str, range_decimation_info, reconstruct_ephemeris, group_into_bursts, azimuth_time, is_test_mode, slant_range_times
 
 import sentinel1decoder.constants as cnst
+import sentinel1decoder.utilities as utilities
+import datetime
+import numpy as np
+import pandas as pd
 import pytest
+import struct
 
 def test_range_dec_to_sample_rate():
     base_sample_freq = 4 * cnst.F_REF
@@ -23,4 +28,160 @@ def test_range_dec_to_sample_rate():
     with pytest.raises(Exception):
         range_dec_to_sample_rate(12)
     with pytest.raises(Exception):
-        range_dec_to_sample_rate(-1)
\ No newline at end of file
+        range_dec_to_sample_rate(-1)
+
+def test_reconstruct_ephemeris():
+    # One complete cycle with X position 7000km, preceded by the tail end of
+    # a cycle and followed by a truncated one
+    cycle_words = list(struct.unpack('>HHHH', struct.pack('>d', 7.0e6))) + [0] * 60
+    adwidx = list(range(60, 65)) + list(range(1, 65)) + list(range(1, 11))
+    adw = [0] * 5 + cycle_words + [0] * 10
+
+    ephemeris = reconstruct_ephemeris(np.array(adwidx), np.array(adw))
+    assert len(ephemeris) == 1
+    assert ephemeris.index[0] == 5
+    assert ephemeris[cnst.X_POS_FIELD_NAME].iloc[0] == 7.0e6
+
+    with pytest.raises(Exception):
+        reconstruct_ephemeris(np.array([1, 2]), np.array([0]))
+
+
+def test_group_into_bursts():
+    df = pd.DataFrame({
+        cnst.SWATH_NUM_FIELD_NAME: [10, 10, 10, 10, 11, 11, 11, 11],
+        cnst.AZIMUTH_BEAM_ADDRESS_FIELD_NAME: [5, 5, 5, 5, 5, 5, 6, 6],
+        cnst.SPACE_PACKET_COUNT_FIELD_NAME: [0, 1, 2, 3, 4, 5, 6, 7],
+        cnst.PRI_COUNT_FIELD_NAME: [0, 1, 2, 3, 4, 5, 6, 7],
+        cnst.SIGNAL_TYPE_FIELD_NAME: [1, 0, 0, 0, 0, 8, 0, 0],
+    })
+    bursts = group_into_bursts(df)
+    assert len(bursts) == 3
+    assert list(bursts[cnst.BURST_START_FIELD_NAME]) == [0, 4, 6]
+    assert list(bursts[cnst.BURST_STOP_FIELD_NAME]) == [3, 5, 7]
+    assert list(bursts[cnst.SWATH_NUM_FIELD_NAME]) == [10, 11, 11]
+    assert list(bursts[cnst.BURST_HAS_NOISE_FIELD_NAME]) == [True, False, False]
+    assert list(bursts[cnst.BURST_HAS_CAL_FIELD_NAME]) == [False, True, False]
+
+
+def test_swap_flag_mismatches():
+    swap = [0, 0, 1, 0, 1, 1, 1, 0]
+    burst = [1, 1, 1, 1, 2, 2, 2, 2]
+    np.testing.assert_array_equal(
+        utilities.swap_flag_mismatches(swap, burst),
+        [False, False, True, False, False, False, False, True]
+    )
+
+
+def test_valid_sample_count():
+    fs = range_dec_to_sample_rate(8)
+    swl = np.array([100.5 / fs, 1000.5 / fs])
+    np.testing.assert_array_equal(utilities.valid_sample_count(swl, [8, 8], [200, 200]), [100, 400])
+
+
+def test_baq_block_layout():
+    assert utilities.baq_block_layout(0) == (0, [])
+    assert utilities.baq_block_layout(128) == (1, [128])
+    assert utilities.baq_block_layout(300) == (3, [128, 128, 44])
+
+
+def test_range_decimation_info():
+    info = range_decimation_info(8)
+    assert info[cnst.RANGE_DEC_NUMERATOR_FIELD_NAME] == 3
+    assert info[cnst.RANGE_DEC_DENOMINATOR_FIELD_NAME] == 7
+    assert info[cnst.RANGE_DEC_FILTER_LENGTH_FIELD_NAME] == 36
+    assert info[cnst.RANGE_DEC_SAMPLE_RATE_FIELD_NAME] == range_dec_to_sample_rate(8)
+
+    with pytest.raises(Exception):
+        range_decimation_info(2)
+
+
+def test_polarisation_str():
+    assert polarisation_str(1) == "HH"
+    assert polarisation_str(2) == "HV"
+    assert polarisation_str(5) == "VH"
+    assert polarisation_str(6) == "VV"
+
+    with pytest.raises(Exception):
+        polarisation_str(8)
+
+
+def test_datation_to_seconds_leap_seconds():
+    # One second either side of the 2017 leap second, in GPS time
+    leap_second = (datetime.datetime(2017, 1, 1) - cnst.GPS_EPOCH).total_seconds() + 18
+    coarse_time = np.array([leap_second - 1, leap_second + 1])
+    fine_time = np.zeros(2)
+
+    gps_times = utilities.datation_to_seconds(coarse_time, fine_time, epoch=cnst.GPS_EPOCH)
+    np.testing.assert_array_equal(gps_times, coarse_time)
+
+    utc_times = utilities.datation_to_seconds(coarse_time, fine_time, epoch=cnst.GPS_EPOCH, leap_seconds=cnst.GPS_UTC_LEAP_SECONDS)
+    np.testing.assert_array_equal(utc_times, coarse_time - [17, 18])
+
+    fixed_offset = utilities.datation_to_seconds(coarse_time, fine_time, epoch=cnst.GPS_EPOCH, leap_seconds=18)
+    np.testing.assert_array_equal(fixed_offset, coarse_time - 18)
+
+
+def test_azimuth_time():
+    pri = np.full(4, 1e-3)
+    coarse_time = np.array([100, 100, 100, 100])
+    fine_time = np.array([0.5, 0.5, 0.5, 0.5])
+
+    # PRI count wraps around between the second and third packets
+    pri_count = np.array([2**32 - 2, 2**32 - 1, 1, 2])
+    times = azimuth_time(coarse_time, fine_time, pri, pri_count, epoch=cnst.GPS_EPOCH)
+    np.testing.assert_allclose(times, 100.5 + np.array([0, 1, 3, 4]) * 1e-3)
+
+
+def test_slant_range_times():
+    pri = np.array([5e-4, 5e-4])
+    swst = np.array([1e-4, 2e-4])
+    near_range, far_range = slant_range_times([9, 10], pri, swst, [1000, 2000], 1e8)
+    np.testing.assert_allclose(near_range, [9 * 5e-4 + 1e-4, 10 * 5e-4 + 2e-4] + cnst.SWST_DELAY)
+    np.testing.assert_allclose(far_range - near_range, [999e-8, 1999e-8])
+
+
+def test_align_by_swst():
+    fs = range_dec_to_sample_rate(8)
+    samples = np.arange(1, 13).reshape(3, 4) + 0j
+    swst = np.array([10e-6, 10e-6 + 2 / fs, 10e-6 + 1 / fs])
+
+    aligned = utilities.align_by_swst(samples, swst, [8, 8, 8])
+    assert aligned.shape == (3, 6)
+    assert aligned.dtype == samples.dtype
+    np.testing.assert_array_equal(aligned[0], [1, 2, 3, 4, 0, 0])
+    np.testing.assert_array_equal(aligned[1], [0, 0, 5, 6, 7, 8])
+    np.testing.assert_array_equal(aligned[2], [0, 9, 10, 11, 12, 0])
+
+    with pytest.raises(ValueError):
+        utilities.align_by_swst(samples, swst, [8, 8, 11])
+
+
+def test_generate_tx_replica_window():
+    unwindowed = utilities.generate_tx_replica(1e12, -1e6, 1e-5, 1e8, 1200)
+    np.testing.assert_array_equal(utilities.generate_tx_replica(1e12, -1e6, 1e-5, 1e8, 1200, window="rectangular"), unwindowed)
+
+    hann = utilities.generate_tx_replica(1e12, -1e6, 1e-5, 1e8, 1200, window="hann")
+    np.testing.assert_allclose(np.abs(hann[:1000]), np.hanning(1000), atol=1e-6)
+    np.testing.assert_array_equal(hann[1000:], 0)
+
+    with pytest.raises(ValueError):
+        utilities.generate_tx_replica(1e12, -1e6, 1e-5, 1e8, 1200, window="kaiser")
+
+
+def test_test_mode():
+    # Imported via the module so pytest doesn't collect test_mode_str as a test
+    assert utilities.test_mode_str(0) == "Default"
+    assert utilities.test_mode_str(7) == "Bypass"
+    with pytest.raises(Exception):
+        utilities.test_mode_str(2)
+
+    np.testing.assert_array_equal(is_test_mode([0, 4, 0, 7]), [False, True, False, True])
+
+
+def test_temperature_compensation_str():
+    assert utilities.temperature_compensation_str(0) == "FE Off, TA Off"
+    assert utilities.temperature_compensation_str(3) == "FE On, TA On"
+    with pytest.raises(Exception):
+        utilities.temperature_compensation_str(4)
+
+    np.testing.assert_array_equal(utilities.is_calibration([0, 1, 1, 0]), [False, True, True, False])